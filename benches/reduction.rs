@@ -0,0 +1,171 @@
+//! Times normal order reductions and normal form checks through the library, the checks
+//! also by walking the term as they did before `Term` cached them, then the rewriting
+//! and the abstract machines on the way to weak head normal forms. `cargo bench` runs it.
+
+use rambda::{
+    ast::{Expr, RedType, Strategy},
+    vm::{Options, Vm},
+};
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+const PRELUDE: &str = r"
+zero := \f.\x.x
+succ := \n.\f.\x.f (n f x)
+plus := \m.\n.\f.\x.m f (n f x)
+mult := \m.\n.\f.m (n f)
+pred := \n.\f.\x.n (\g.\h.h (g f)) (\u.x) (\u.u)
+true := \x.\y.x
+false := \x.\y.y
+iszero := \n.n (\x.false) true
+Y := \f.(\x.f (x x)) (\x.f (x x))
+fact := Y (\r.\n.(iszero n) (succ zero) (mult n (r (pred n))))
+two := succ (succ zero)
+three := succ two
+four := plus two two
+five := succ four
+ten := plus five five
+not := \b.b false true
+";
+
+// Runs per case, the fastest is reported as the least disturbed by other processes
+const RUNS: usize = 5;
+
+// Normal form checks timed on each result
+const CHECKS: u32 = 1000;
+
+const CASES: &[&str] = &[
+    "plus ten ten",
+    "mult ten ten",
+    "fact three",
+    "fact four",
+    "fact five",
+];
+
+// Closed terms whose weak head normal form takes the whole computation, the numerals
+// apply `not` to `true`
+const WHNF_CASES: &[&str] = &[
+    "mult ten ten not true",
+    "fact three not true",
+    "fact four not true",
+    "fact five not true",
+];
+
+fn main() {
+    normal_forms();
+    println!();
+    weak_head_normal_forms();
+}
+
+fn normal_forms() {
+    println!(
+        "{:<14} {:>8} {:>12} {:>12} {:>12} {:>12}",
+        "", "steps", "evaluate", "stream", "normal form", "walk"
+    );
+    for input in CASES {
+        let mut vm = Vm::with_options(Options {
+            prelude: PRELUDE.to_string(),
+            ..Options::default()
+        })
+        .expect("prelude parses");
+
+        // Keeps every step, as `eval` does
+        let mut steps = 0;
+        let evaluate = fastest(|| {
+            let evaluation = vm.evaluate(black_box(input)).expect("input parses");
+            steps = evaluation.steps.len();
+        });
+
+        // Only the current term is alive, as in the REPL and the TUI
+        let stream = fastest(|| {
+            vm.parse_expr(black_box(input)).expect("input parses");
+            vm.steps().for_each(drop);
+        });
+
+        let check = fastest(|| {
+            for _ in 0..CHECKS {
+                black_box(vm.is_normal_form());
+            }
+        }) / CHECKS;
+        let result = vm.get_expr().cloned().expect("evaluated");
+        let walk = fastest(|| {
+            for _ in 0..CHECKS {
+                black_box(walk_normal_form(black_box(&result)));
+            }
+        }) / CHECKS;
+
+        println!(
+            "{:<14} {:>8} {:>12.2?} {:>12.2?} {:>12.2?} {:>12.2?}",
+            input, steps, evaluate, stream, check, walk
+        );
+    }
+}
+
+fn weak_head_normal_forms() {
+    println!(
+        "{:<22} {:>8} {:>12} {:>12} {:>12} {:>12}",
+        "", "beta", "rewrite", "krivine", "compile", "bytecode"
+    );
+    for input in WHNF_CASES {
+        let mut vm = Vm::with_options(Options {
+            prelude: PRELUDE.to_string(),
+            ..Options::default()
+        })
+        .expect("prelude parses");
+        let expr = vm.parse(input).expect("input parses");
+
+        // Normal order contracts the head redex until the term is an abstraction
+        let mut beta = 0;
+        let rewrite = fastest(|| {
+            let mut expr = black_box(expr.clone());
+            beta = 0;
+            while !matches!(expr, Expr::Abs(..)) {
+                let (next, red, _) = expr.reduce_in(Strategy::NormalOrder, vm.definitions());
+                beta += matches!(red, RedType::BetaReduction(_)) as usize;
+                expr = next;
+            }
+        });
+
+        let krivine = fastest(|| {
+            let run = vm.evaluate_krivine(black_box(&expr), false);
+            assert_eq!(run.expect("closed").beta, beta);
+        });
+
+        let compile = fastest(|| {
+            black_box(vm.compile(black_box(&expr)).expect("closed"));
+        });
+        let program = vm.compile(&expr).expect("closed");
+        let bytecode = fastest(|| {
+            assert_eq!(vm.run_program(black_box(&program)).beta, beta);
+        });
+
+        println!(
+            "{:<22} {:>8} {:>12.2?} {:>12.2?} {:>12.2?} {:>12.2?}",
+            input, beta, rewrite, krivine, compile, bytecode
+        );
+    }
+}
+
+// The normal form check without the cached flags, down to every leaf
+fn walk_normal_form(expr: &Expr) -> bool {
+    match expr {
+        Expr::Var(_) => true,
+        Expr::Abs(_, body) => walk_normal_form(body),
+        Expr::Apl(func, arg) => {
+            !matches!(**func, Expr::Abs(..)) && walk_normal_form(func) && walk_normal_form(arg)
+        }
+    }
+}
+
+fn fastest(mut run: impl FnMut()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let started = Instant::now();
+            run();
+            started.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
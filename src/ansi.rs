@@ -0,0 +1,80 @@
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+// History lines carry the ANSI colors of the terminal printers, translate the SGR codes
+// they use into styles. A sequence cut off by the typing effect is dropped.
+pub fn to_spans(line: &str) -> Spans<'static> {
+    let mut spans = Vec::new();
+    let mut style = Style::default();
+    let mut text = String::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            text.push(c);
+            continue;
+        }
+        if chars.next_if_eq(&'[').is_none() {
+            continue;
+        }
+        let mut params = String::new();
+        let mut terminated = false;
+        for c in chars.by_ref() {
+            if c == 'm' {
+                terminated = true;
+                break;
+            }
+            params.push(c);
+        }
+        if !terminated {
+            break;
+        }
+
+        if !text.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut text), style));
+        }
+        let codes = params
+            .split(';')
+            .map(|code| code.parse::<u8>().unwrap_or(0))
+            .collect::<Vec<_>>();
+        let mut codes = codes.iter();
+        while let Some(code) = codes.next() {
+            match code {
+                0 => style = Style::default(),
+                1 => style = style.add_modifier(Modifier::BOLD),
+                4 => style = style.add_modifier(Modifier::UNDERLINED),
+                38 | 48 => {
+                    if let (Some(5), Some(&n)) = (codes.next(), codes.next()) {
+                        style = if *code == 38 {
+                            style.fg(Color::Indexed(n))
+                        } else {
+                            style.bg(Color::Indexed(n))
+                        };
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    if !text.is_empty() {
+        spans.push(Span::styled(text, style));
+    }
+
+    Spans::from(spans)
+}
+
+/// The printable text of a line, without its escape sequences.
+pub fn strip(line: &str) -> String {
+    let mut text = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| *c == 'm');
+        } else {
+            text.push(c);
+        }
+    }
+    text
+}
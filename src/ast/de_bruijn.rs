@@ -0,0 +1,123 @@
+use super::{Expr, Term};
+use std::{collections::HashSet, fmt};
+
+/// A term with its bound variables as de Bruijn indices, 0 being the nearest binder.
+/// Free variables keep their names, and binders theirs as hints for turning back into
+/// an `Expr`. Terms that only differ in the names of bound variables are equal.
+#[derive(Debug, Clone)]
+pub enum DeBruijn {
+    Var(usize),
+    Free(String),
+    Abs(String, Box<DeBruijn>),
+    Apl(Box<DeBruijn>, Box<DeBruijn>),
+}
+
+impl DeBruijn {
+    /// Back to a term, binders named after their hints unless that would capture or
+    /// shadow another variable.
+    pub fn to_expr(&self) -> Expr {
+        let mut free = HashSet::new();
+        self.collect_free(&mut free);
+        self.to_expr_in(&free, &mut Vec::new())
+    }
+
+    fn to_expr_in(&self, free: &HashSet<&str>, scope: &mut Vec<String>) -> Expr {
+        match self {
+            DeBruijn::Var(index) => Expr::Var(scope[scope.len() - 1 - index].clone()),
+            DeBruijn::Free(name) => Expr::Var(name.clone()),
+            DeBruijn::Abs(hint, body) => {
+                let mut name = hint.clone();
+                let mut counter = 0;
+                while free.contains(name.as_str()) || scope.contains(&name) {
+                    counter += 1;
+                    name = format!("{}_{}", hint, counter);
+                }
+                scope.push(name);
+                let body = body.to_expr_in(free, scope);
+                let name = scope.pop().unwrap_or_default();
+                Expr::Abs(name, Term::new(body))
+            }
+            DeBruijn::Apl(func, arg) => Expr::Apl(
+                Term::new(func.to_expr_in(free, scope)),
+                Term::new(arg.to_expr_in(free, scope)),
+            ),
+        }
+    }
+
+    fn collect_free<'a>(&'a self, free: &mut HashSet<&'a str>) {
+        match self {
+            DeBruijn::Var(_) => {}
+            DeBruijn::Free(name) => {
+                free.insert(name);
+            }
+            DeBruijn::Abs(_, body) => body.collect_free(free),
+            DeBruijn::Apl(func, arg) => {
+                func.collect_free(free);
+                arg.collect_free(free);
+            }
+        }
+    }
+}
+
+impl From<&Expr> for DeBruijn {
+    fn from(expr: &Expr) -> Self {
+        from_expr(expr, &mut Vec::new())
+    }
+}
+
+// `bound` has the enclosing binders, the nearest last
+fn from_expr<'a>(expr: &'a Expr, bound: &mut Vec<&'a str>) -> DeBruijn {
+    match expr {
+        Expr::Var(name) => match bound.iter().rposition(|param| param == name) {
+            Some(position) => DeBruijn::Var(bound.len() - 1 - position),
+            None => DeBruijn::Free(name.clone()),
+        },
+        Expr::Abs(param, body) => {
+            bound.push(param);
+            let body = from_expr(body, bound);
+            bound.pop();
+            DeBruijn::Abs(param.clone(), Box::new(body))
+        }
+        Expr::Apl(func, arg) => DeBruijn::Apl(
+            Box::new(from_expr(func, bound)),
+            Box::new(from_expr(arg, bound)),
+        ),
+    }
+}
+
+// Binder names are only hints
+impl PartialEq for DeBruijn {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (DeBruijn::Var(a), DeBruijn::Var(b)) => a == b,
+            (DeBruijn::Free(a), DeBruijn::Free(b)) => a == b,
+            (DeBruijn::Abs(_, a), DeBruijn::Abs(_, b)) => a == b,
+            (DeBruijn::Apl(func_a, arg_a), DeBruijn::Apl(func_b, arg_b)) => {
+                func_a == func_b && arg_a == arg_b
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for DeBruijn {}
+
+impl fmt::Display for DeBruijn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeBruijn::Var(index) => write!(f, "{}", index),
+            DeBruijn::Free(name) => f.write_str(name),
+            DeBruijn::Abs(_, body) => write!(f, "λ {}", body),
+            DeBruijn::Apl(func, arg) => {
+                match **func {
+                    DeBruijn::Abs(..) => write!(f, "({})", func)?,
+                    _ => write!(f, "{}", func)?,
+                }
+                match **arg {
+                    DeBruijn::Abs(..) | DeBruijn::Apl(..) => write!(f, " ({})", arg),
+                    _ => write!(f, " {}", arg),
+                }
+            }
+        }
+    }
+}
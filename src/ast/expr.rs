@@ -1,5 +1,8 @@
-use super::RedType;
-use std::{collections::HashSet, rc::Rc};
+use super::{Branch, Path, RedType, Strategy, Term};
+use std::collections::{BTreeMap, HashSet};
+
+// Named terms that free variables of the same name unfold to
+pub type Definitions = BTreeMap<String, Expr>;
 
 #[derive(Debug, Clone)]
 pub enum Expr {
@@ -7,113 +10,138 @@ pub enum Expr {
     Var(String),
 
     // Abstraction: λx.e (a function with parameter and body)
-    Abs(String, Rc<Expr>),
+    Abs(String, Term),
 
     // Apllication: e1 e2 (function application)
-    Apl(Rc<Expr>, Rc<Expr>),
+    Apl(Term, Term),
 }
 
 impl Expr {
-    pub fn fmt_with_config(&self, color: bool, ascii: bool, merge: bool) -> String {
+    pub fn fmt_with_config(&self, color: bool, utf8: bool, merge: bool) -> String {
+        self.fmt_highlighted(color, utf8, merge, &[])
+    }
+
+    /// Like `fmt_with_config`, but when `color` is set the subterm at each path gets
+    /// the paired ANSI code (usually a background color) applied on top.
+    pub fn fmt_highlighted(
+        &self,
+        color: bool,
+        utf8: bool,
+        merge: bool,
+        highlights: &[(&[Branch], &str)],
+    ) -> String {
         let reset = if color { "\x1b[0m" } else { "" };
+        self.fmt_inner(color, utf8, merge, highlights, reset)
+    }
+
+    // `reset` restores the highlights of the enclosing subterms after every colored token
+    fn fmt_inner(
+        &self,
+        color: bool,
+        utf8: bool,
+        merge: bool,
+        highlights: &[(&[Branch], &str)],
+        outer_reset: &str,
+    ) -> String {
+        let mut reset = outer_reset.to_string();
+        let mut prefix = String::new();
+        if color {
+            for (_, code) in highlights.iter().filter(|(path, _)| path.is_empty()) {
+                prefix.push_str(code);
+                reset.push_str(code);
+            }
+        }
         let lambda = if color { "\x1b[1m\x1b[38;5;2m" } else { "" };
+        let lambda_char = if utf8 { "λ" } else { "\\" };
         let var = if color { "\x1b[1m\x1b[38;5;4m" } else { "" };
-        let gray = if color { "\x1b[0m\x1b[38;5;240m" } else { "" };
+        let gray = if color {
+            format!("{}\x1b[38;5;240m", reset)
+        } else {
+            String::new()
+        };
 
-        match self {
+        let body = match self {
             Expr::Var(name) => name.clone(),
-            Expr::Abs(from, to) => {
-                if merge {
-                    let (params, body) = self.collect_abstractions();
+            Expr::Abs(param, body) => {
+                let mut params = vec![param.clone()];
+                let mut body = body;
+                let mut inner = descend(highlights, Branch::Body);
+                // Merged binders stop where a highlight starts
+                while merge && !inner.iter().any(|(path, _)| path.is_empty()) {
+                    let Expr::Abs(param, next) = &**body else {
+                        break;
+                    };
+                    params.push(param.clone());
+                    body = next;
+                    inner = descend(&inner, Branch::Body);
+                }
+                format!(
+                    "{}{}{}{}{}.{}{}",
+                    lambda,
+                    lambda_char,
+                    var,
+                    params.join(","),
+                    gray,
+                    reset,
+                    body.fmt_inner(color, utf8, merge, &inner, &reset),
+                )
+            }
+            Expr::Apl(e1, e2) => {
+                let paren = |expr: &Expr, highlights: &[(&[Branch], &str)]| {
                     format!(
-                        "{}λ{}{}{}.{}{}",
-                        lambda,
-                        var,
-                        params.join(","),
+                        "{}({}{}{}){}",
                         gray,
                         reset,
-                        body.fmt_with_config(color, ascii, merge),
-                    )
-                } else {
-                    format!(
-                        "{}λ{}{}{}.{}{}",
-                        lambda,
-                        var,
-                        from,
+                        expr.fmt_inner(color, utf8, merge, highlights, &reset),
                         gray,
-                        reset,
-                        to.fmt_with_config(color, ascii, merge),
+                        reset
                     )
-                }
+                };
+                let func = descend(highlights, Branch::Func);
+                let arg = descend(highlights, Branch::Arg);
+                // Application is left associative, so only an abstraction needs parens on the left
+                let func = match &**e1 {
+                    Expr::Abs(_, _) => paren(e1, &func),
+                    _ => e1.fmt_inner(color, utf8, merge, &func, &reset),
+                };
+                let arg = match &**e2 {
+                    Expr::Var(_) => e2.fmt_inner(color, utf8, merge, &arg, &reset),
+                    _ => paren(e2, &arg),
+                };
+                format!("{} {}", func, arg)
             }
-            Expr::Apl(_, _) => {
-                let apps = self.collect_applications();
-
-                let mut parts = Vec::new();
-                for (i, expr) in apps.iter().enumerate() {
-                    let s = match expr {
-                        Expr::Apl(_, _) | Expr::Abs(_, _) if i != 0 => {
-                            format!(
-                                "{}({}{}{}){}",
-                                gray,
-                                reset,
-                                expr.fmt_with_config(color, ascii, merge),
-                                gray,
-                                reset
-                            )
-                        }
-                        _ => expr.fmt_with_config(color, ascii, merge),
-                    };
-                    parts.push(s);
-                }
-
-                parts.join(" ")
-            }
-        }
-    }
-
-    fn collect_abstractions(&self) -> (Vec<String>, &Expr) {
-        let mut params = Vec::new();
-        let mut current = self;
-
-        while let Expr::Abs(param, body) = current {
-            params.push(param.clone());
-            current = body;
-        }
-
-        (params, current)
-    }
-
-    fn collect_applications(&self) -> Vec<&Expr> {
-        let mut apps = Vec::new();
-        let mut current = self;
+        };
 
-        while let Expr::Apl(e1, e2) = current {
-            apps.push(e2.as_ref());
-            current = e1.as_ref();
+        if prefix.is_empty() {
+            body
+        } else {
+            format!("{}{}{}", prefix, body, outer_reset)
         }
-
-        apps.push(current);
-        apps.reverse();
-        apps
     }
 
     pub fn free_vars(&self) -> HashSet<String> {
         match self {
             Expr::Var(name) => [name.clone()].into_iter().collect(),
-            Expr::Abs(param, body) => {
-                let mut vars = body.free_vars();
-                vars.remove(param);
-                vars
-            }
-            Expr::Apl(e1, e2) => {
-                let mut vars = e1.free_vars();
-                vars.extend(e2.free_vars());
-                vars
-            }
+            Expr::Abs(param, body) => body
+                .free()
+                .iter()
+                .filter(|name| ***name != **param)
+                .map(|name| name.to_string())
+                .collect(),
+            Expr::Apl(e1, e2) => e1
+                .free()
+                .iter()
+                .chain(e2.free())
+                .map(|name| name.to_string())
+                .collect(),
         }
     }
 
+    /// Whether the terms are the same up to the names of bound variables.
+    pub fn alpha_eq(&self, other: &Expr) -> bool {
+        alpha_eq_in(self, other, &mut Vec::new())
+    }
+
     pub fn bound_vars(&self) -> HashSet<String> {
         match self {
             Expr::Var(_) => HashSet::new(),
@@ -135,16 +163,18 @@ impl Expr {
     fn is_free_in(&self, var: &str) -> bool {
         match self {
             Expr::Var(name) => name == var,
-            Expr::Abs(param, body) => param != var && body.is_free_in(var),
-            Expr::Apl(e1, e2) => e1.is_free_in(var) || e2.is_free_in(var),
+            Expr::Abs(param, body) => param != var && body.has_free(var),
+            Expr::Apl(e1, e2) => e1.has_free(var) || e2.has_free(var),
         }
     }
 
-    fn fresh_var(&self, hint: &str) -> String {
+    fn fresh_var(&self, hint: &str, avoid: &Expr) -> String {
+        let bound = self.bound_vars();
         let mut new_name = hint.to_string();
         let mut counter = 0;
 
-        while self.is_free_in(&new_name) {
+        while self.is_free_in(&new_name) || avoid.is_free_in(&new_name) || bound.contains(&new_name)
+        {
             counter += 1;
             new_name = format!("{}_{}", hint, counter);
         }
@@ -152,47 +182,107 @@ impl Expr {
         new_name
     }
 
-    fn substitute_once(&self, var: &str, replacement: &Expr) -> (Expr, RedType) {
+    // Renames the first binder that would capture a free variable of `replacement`
+    // when substituting `var`, None means the substitution is capture-free
+    fn rename_capturing(&self, var: &str, replacement: &Expr) -> Option<(Expr, RedType)> {
         match self {
-            Expr::Var(name) if name == var => {
-                (replacement.clone(), RedType::BetaReduction(var.to_string()))
-            }
-            Expr::Var(_) => (self.clone(), RedType::NoReduction),
+            _ if !self.is_free_in(var) => None,
+            Expr::Var(_) => None,
             Expr::Abs(param, body) => {
-                if param == var {
-                    (self.clone(), RedType::NoReduction)
-                } else if replacement.is_free_in(param) {
-                    let fresh = body.fresh_var(param);
-                    let renamed_body = body.substitute_once(param, &Expr::Var(fresh.clone())).0;
-                    (
-                        Expr::Abs(fresh.clone(), Rc::new(renamed_body)),
+                if replacement.is_free_in(param) {
+                    let fresh = body.fresh_var(param, replacement);
+                    let renamed_body = body.rename_var(param, &fresh);
+                    Some((
+                        Expr::Abs(fresh.clone(), Term::new(renamed_body)),
                         RedType::AlphaConversion(param.clone(), fresh),
-                    )
+                    ))
                 } else {
-                    let (new_body, red) = body.substitute_once(var, replacement);
-                    (Expr::Abs(param.clone(), Rc::new(new_body)), red)
+                    let (new_body, red) = body.rename_capturing(var, replacement)?;
+                    Some((Expr::Abs(param.clone(), Term::new(new_body)), red))
                 }
             }
             Expr::Apl(e1, e2) => {
-                let (new_e1, red1) = e1.substitute_once(var, replacement);
-                if red1 != RedType::NoReduction {
-                    (Expr::Apl(Rc::new(new_e1), e2.clone()), red1)
-                } else {
-                    let (new_e2, red2) = e2.substitute_once(var, replacement);
-                    if red2 != RedType::NoReduction {
-                        (Expr::Apl(e1.clone(), Rc::new(new_e2)), red2)
-                    } else {
-                        (self.clone(), RedType::NoReduction)
-                    }
+                if let Some((new_e1, red)) = e1.rename_capturing(var, replacement) {
+                    return Some((Expr::Apl(Term::new(new_e1), e2.clone()), red));
                 }
+                let (new_e2, red) = e2.rename_capturing(var, replacement)?;
+                Some((Expr::Apl(e1.clone(), Term::new(new_e2)), red))
             }
         }
     }
 
+    // Plain substitution, callers make sure no binder in `self` captures `replacement`
+    fn substitute(&self, var: &str, replacement: &Term) -> Expr {
+        match self {
+            _ if !self.is_free_in(var) => self.clone(),
+            Expr::Var(_) => (**replacement).clone(),
+            Expr::Abs(param, body) => {
+                Expr::Abs(param.clone(), substitute_in(body, var, replacement))
+            }
+            Expr::Apl(e1, e2) => Expr::Apl(
+                substitute_in(e1, var, replacement),
+                substitute_in(e2, var, replacement),
+            ),
+        }
+    }
+
+    // (λparam.body) arg → body[param := arg], preceded by α-conversions when needed
+    fn contract(param: &str, body: &Term, arg: &Term) -> (Expr, RedType) {
+        if let Some((renamed_body, red)) = body.rename_capturing(param, arg) {
+            let abs = Expr::Abs(param.to_string(), Term::new(renamed_body));
+            return (Expr::Apl(Term::new(abs), arg.clone()), red);
+        }
+        (
+            body.substitute(param, arg),
+            RedType::BetaReduction(param.to_string()),
+        )
+    }
+
     pub fn is_redex(&self) -> bool {
         matches!(self, Expr::Apl(e1, _) if matches!(**e1, Expr::Abs(_, _)))
     }
 
+    /// Position of the redex the given strategy contracts next, `None` in normal form.
+    pub fn redex_path(&self, strategy: Strategy) -> Option<Path> {
+        if self.is_normal_form() {
+            return None;
+        }
+        let (branch, child) = match self {
+            Expr::Var(_) => return None,
+            Expr::Abs(_, body) => (Branch::Body, body.redex_path(strategy)),
+            Expr::Apl(e1, e2) => {
+                if strategy == Strategy::NormalOrder && self.is_redex() {
+                    return Some(Path::new());
+                }
+                if let Some(path) = e1.redex_path(strategy) {
+                    (Branch::Func, Some(path))
+                } else if let Some(path) = e2.redex_path(strategy) {
+                    (Branch::Arg, Some(path))
+                } else if self.is_redex() {
+                    return Some(Path::new());
+                } else {
+                    return None;
+                }
+            }
+        };
+        let mut path = child?;
+        path.insert(0, branch);
+        Some(path)
+    }
+
+    pub fn at_path(&self, path: &[Branch]) -> Option<&Expr> {
+        let Some((branch, rest)) = path.split_first() else {
+            return Some(self);
+        };
+        match (self, branch) {
+            (Expr::Abs(_, body), Branch::Body) => body.at_path(rest),
+            (Expr::Apl(e1, _), Branch::Func) => e1.at_path(rest),
+            (Expr::Apl(_, e2), Branch::Arg) => e2.at_path(rest),
+            _ => None,
+        }
+    }
+
+    // Children cache their own flags, so this and the measures below only look one level down
     pub fn is_normal_form(&self) -> bool {
         match self {
             Expr::Var(_) => true,
@@ -201,53 +291,186 @@ impl Expr {
         }
     }
 
-    pub fn eval_step(&self) -> (Expr, RedType) {
+    /// Normal form with respect to `definitions` as well, so no defined name is left to unfold.
+    pub fn is_normal_form_in(&self, definitions: &Definitions) -> bool {
+        self.is_normal_form() && !self.unfolds_in(definitions, &[])
+    }
+
+    // Whether a free name of `self` is defined and not bound by the enclosing binders
+    fn unfolds_in(&self, definitions: &Definitions, bound: &[&str]) -> bool {
+        if definitions.is_empty() {
+            return false;
+        }
+        let defined = |name: &str| definitions.contains_key(name) && !bound.contains(&name);
+        match self {
+            Expr::Var(name) => defined(name),
+            Expr::Abs(param, body) => body
+                .free()
+                .iter()
+                .any(|name| **name != **param && defined(name)),
+            Expr::Apl(e1, e2) => e1.free().iter().chain(e2.free()).any(|name| defined(name)),
+        }
+    }
+
+    /// Longest path from the root to a variable, a variable alone has depth 1.
+    pub fn depth(&self) -> usize {
+        match self {
+            Expr::Var(_) => 1,
+            Expr::Abs(_, body) => 1 + body.depth(),
+            Expr::Apl(e1, e2) => 1 + e1.depth().max(e2.depth()),
+        }
+    }
+
+    /// Free occurrences of `var`, the places a β-reduction binding it substitutes.
+    pub fn occurrences(&self, var: &str) -> usize {
+        match self {
+            _ if !self.is_free_in(var) => 0,
+            Expr::Var(_) => 1,
+            Expr::Abs(_, body) => body.occurrences(var),
+            Expr::Apl(e1, e2) => e1.occurrences(var) + e2.occurrences(var),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Expr::Var(_) => 1,
+            Expr::Abs(_, body) => 1 + body.size(),
+            Expr::Apl(e1, e2) => 1 + e1.size() + e2.size(),
+        }
+    }
+
+    pub fn eval_step(&self) -> (Expr, RedType, Path) {
+        self.eval_step_with(Strategy::NormalOrder)
+    }
+
+    pub fn eval_step_with(&self, strategy: Strategy) -> (Expr, RedType, Path) {
+        self.eval_step_in(strategy, &Definitions::new())
+    }
+
+    /// Performs one reduction, returning the new term, the kind of step and the
+    /// position of the contracted redex, which is also where its contractum sits now.
+    /// Free occurrences of names in `definitions` are unfolded when the strategy reaches them.
+    pub fn eval_step_in(
+        &self,
+        strategy: Strategy,
+        definitions: &Definitions,
+    ) -> (Expr, RedType, Path) {
+        let (expr, red, path) = self.reduce_in(strategy, definitions);
+        (expr, red.in_context_of(&path), path)
+    }
+
+    /// Like `eval_step_in`, but reports a reduction inside an application as what it is
+    /// instead of a contextual one.
+    pub fn reduce_in(
+        &self,
+        strategy: Strategy,
+        definitions: &Definitions,
+    ) -> (Expr, RedType, Path) {
+        match self.step_in(strategy, definitions, &mut Vec::new()) {
+            Some((expr, red, mut path)) => {
+                path.reverse();
+                (expr, red, path)
+            }
+            None => (self.clone(), RedType::NoReduction, Path::new()),
+        }
+    }
+
+    // `None` when there is nothing to reduce, the path is built innermost branch first
+    fn step_in<'a>(
+        &'a self,
+        strategy: Strategy,
+        definitions: &Definitions,
+        bound: &mut Vec<&'a str>,
+    ) -> Option<(Expr, RedType, Path)> {
+        // Nothing below to contract or unfold, skip the walk
+        if self.is_normal_form() && !self.unfolds_in(definitions, bound) {
+            return None;
+        }
         match self {
             Expr::Apl(e1, e2) => {
-                if let Expr::Abs(param, body) = &**e1 {
-                    return body.substitute_once(param, &**e2);
+                if strategy == Strategy::NormalOrder
+                    && let Expr::Abs(param, body) = &**e1
+                {
+                    let (expr, red) = Self::contract(param, body, e2);
+                    return Some((expr, red, Path::new()));
                 }
 
-                let (reduced_e1, red1) = e1.eval_step();
-                if red1 != RedType::NoReduction {
-                    return (
-                        Expr::Apl(Rc::new(reduced_e1), e2.clone()),
-                        RedType::ContextualReduction("l".to_string()),
-                    );
+                if let Some((reduced_e1, red, mut path)) = e1.step_in(strategy, definitions, bound)
+                {
+                    path.push(Branch::Func);
+                    return Some((Expr::Apl(Term::new(reduced_e1), e2.clone()), red, path));
                 }
-
-                let (reduced_e2, red2) = e2.eval_step();
-                if red2 != RedType::NoReduction {
-                    return (
-                        Expr::Apl(e1.clone(), Rc::new(reduced_e2)),
-                        RedType::ContextualReduction("r".to_string()),
-                    );
+                if let Some((reduced_e2, red, mut path)) = e2.step_in(strategy, definitions, bound)
+                {
+                    path.push(Branch::Arg);
+                    return Some((Expr::Apl(e1.clone(), Term::new(reduced_e2)), red, path));
                 }
 
-                (self.clone(), RedType::NoReduction)
+                let Expr::Abs(param, body) = &**e1 else {
+                    return None;
+                };
+                let (expr, red) = Self::contract(param, body, e2);
+                Some((expr, red, Path::new()))
             }
             Expr::Abs(param, body) => {
-                let (reduced_body, red) = body.eval_step();
-                if red != RedType::NoReduction {
-                    return (Expr::Abs(param.clone(), Rc::new(reduced_body)), red);
+                bound.push(param);
+                let reduced = body.step_in(strategy, definitions, bound);
+                bound.pop();
+                let (reduced_body, red, mut path) = reduced?;
+
+                // A definition unfolded below would have its free names captured by this binder
+                if let RedType::DeltaReduction(name) = &red
+                    && definitions[name].is_free_in(param)
+                {
+                    let fresh = body.fresh_var(param, &definitions[name]);
+                    return Some((
+                        Expr::Abs(fresh.clone(), Term::new(body.rename_var(param, &fresh))),
+                        RedType::AlphaConversion(param.clone(), fresh),
+                        Path::new(),
+                    ));
                 }
-                (self.clone(), RedType::NoReduction)
+                path.push(Branch::Body);
+                Some((Expr::Abs(param.clone(), Term::new(reduced_body)), red, path))
+            }
+            Expr::Var(name) => {
+                let definition = definitions.get(name)?;
+                if bound.contains(&name.as_str()) {
+                    return None;
+                }
+                Some((
+                    definition.clone(),
+                    RedType::DeltaReduction(name.clone()),
+                    Path::new(),
+                ))
             }
-            Expr::Var(_) => (self.clone(), RedType::NoReduction),
         }
     }
 
-    pub fn eval_full(&self) -> (Expr, Vec<RedType>) {
-        let mut reductions = Vec::new();
+    /// Lazily yields each normal order reduction with the term it gives, until a normal form.
+    pub fn reductions(&self) -> impl Iterator<Item = (RedType, Expr)> {
         let mut expr = self.clone();
-        while !expr.is_normal_form() {
-            let (next_expr, reduction_type) = expr.eval_step();
+        std::iter::from_fn(move || {
+            if expr.is_normal_form() {
+                return None;
+            }
+            let (next_expr, reduction_type, _) = expr.eval_step();
             if reduction_type == RedType::NoReduction {
-                break;
+                return None;
             }
-            expr = next_expr;
-            reductions.push(reduction_type);
-        }
+            expr = next_expr.clone();
+            Some((reduction_type, next_expr))
+        })
+    }
+
+    pub fn eval_full(&self) -> (Expr, Vec<RedType>) {
+        let mut expr = self.clone();
+        let reductions = self
+            .reductions()
+            .map(|(reduction_type, next_expr)| {
+                expr = next_expr;
+                reduction_type
+            })
+            .collect();
         (expr, reductions)
     }
 
@@ -280,14 +503,11 @@ impl Expr {
 
     fn rename_var(&self, from: &str, to: &str) -> Expr {
         match self {
-            Expr::Var(name) if name == from => Expr::Var(to.to_string()),
-            Expr::Var(_) => self.clone(),
-            Expr::Abs(param, _) if param == from => self.clone(), // shadowed, stop
-            Expr::Abs(param, body) => Expr::Abs(param.clone(), Rc::new(body.rename_var(from, to))),
-            Expr::Apl(e1, e2) => Expr::Apl(
-                Rc::new(e1.rename_var(from, to)),
-                Rc::new(e2.rename_var(from, to)),
-            ),
+            // Also covers a binder shadowing `from`
+            _ if !self.is_free_in(from) => self.clone(),
+            Expr::Var(_) => Expr::Var(to.to_string()),
+            Expr::Abs(param, body) => Expr::Abs(param.clone(), rename_in(body, from, to)),
+            Expr::Apl(e1, e2) => Expr::Apl(rename_in(e1, from, to), rename_in(e2, from, to)),
         }
     }
 
@@ -295,46 +515,31 @@ impl Expr {
         let mut reductions = Vec::new();
         let mut expr = self.clone();
         match self {
-            Expr::Var(name) => {
-                if let Some((base, num)) = Self::split_name_number(name) {
-                    if num == 1 {
-                        let base = base.trim_end_matches('_').to_string();
-                        expr = Expr::Var(base.clone());
-                        reductions.push(RedType::AlphaConversion(name.clone(), base));
-                    } else {
-                        let new_name = format!("{}_{}", base, num - 1);
-                        if self.is_free_in(&new_name) {
-                            expr = Expr::Var(new_name.clone());
-                            reductions.push(RedType::AlphaConversion(name.clone(), new_name));
-                        }
-                    }
-                }
-            }
+            // Free variables keep their names, bound ones are renamed at their binder
+            Expr::Var(_) => {}
             Expr::Abs(param, body) => {
                 let mut param = param.clone();
                 let mut body = body.clone();
 
                 // Try simplifying the bound var name
-                if let Some((base, num)) = Self::split_name_number(&param) {
-                    if num == 1 {
-                        let base = base.trim_end_matches('_').to_string();
-                        if !body.is_free_in(&base) {
-                            // Rename both the parameter and its uses in the body
-                            body = body.rename_var(&param, &base).into();
-                            reductions.push(RedType::AlphaConversion(param.clone(), base.clone()));
-                            param = base;
-                        }
+                if let Some((base, 1)) = Self::split_name_number(&param) {
+                    let base = base.trim_end_matches('_').to_string();
+                    if !body.is_free_in(&base) && !body.bound_vars().contains(&base) {
+                        // Rename both the parameter and its uses in the body
+                        body = body.rename_var(&param, &base).into();
+                        reductions.push(RedType::AlphaConversion(param.clone(), base.clone()));
+                        param = base;
                     }
                 }
 
                 let (new_body, reds) = body.simplify_numbered_vars();
-                expr = Expr::Abs(param, Rc::new(new_body));
+                expr = Expr::Abs(param, Term::new(new_body));
                 reductions.extend(reds);
             }
             Expr::Apl(e1, e2) => {
                 let (new_e1, reds_e1) = e1.simplify_numbered_vars();
                 let (new_e2, reds_e2) = e2.simplify_numbered_vars();
-                expr = Expr::Apl(Rc::new(new_e1), Rc::new(new_e2));
+                expr = Expr::Apl(Term::new(new_e1), Term::new(new_e2));
                 reductions.extend(reds_e1);
                 reductions.extend(reds_e2);
             }
@@ -349,9 +554,61 @@ pub fn var(name: &str) -> Expr {
 }
 
 pub fn abs(param: &str, body: Expr) -> Expr {
-    Expr::Abs(param.to_string(), Rc::new(body))
+    Expr::Abs(param.to_string(), Term::new(body))
 }
 
 pub fn apl(e1: Expr, e2: Expr) -> Expr {
-    Expr::Apl(Rc::new(e1), Rc::new(e2))
+    Expr::Apl(Term::new(e1), Term::new(e2))
+}
+
+// Subterms without `var` free are shared as they are instead of being rebuilt
+fn substitute_in(term: &Term, var: &str, replacement: &Term) -> Term {
+    match &**term {
+        _ if !term.has_free(var) => term.clone(),
+        Expr::Var(_) => replacement.clone(),
+        expr => Term::new(expr.substitute(var, replacement)),
+    }
+}
+
+// `bound` pairs the binders of both terms from the outermost, a variable bound on one
+// side has to be bound by the matching binder on the other
+fn alpha_eq_in<'a>(a: &'a Expr, b: &'a Expr, bound: &mut Vec<(&'a str, &'a str)>) -> bool {
+    match (a, b) {
+        (Expr::Var(a), Expr::Var(b)) => {
+            let binder_a = bound.iter().rposition(|(name, _)| name == a);
+            let binder_b = bound.iter().rposition(|(_, name)| name == b);
+            binder_a == binder_b && (binder_a.is_some() || a == b)
+        }
+        (Expr::Abs(param_a, body_a), Expr::Abs(param_b, body_b)) => {
+            bound.push((param_a, param_b));
+            let eq = alpha_eq_in(body_a, body_b, bound);
+            bound.pop();
+            eq
+        }
+        (Expr::Apl(func_a, arg_a), Expr::Apl(func_b, arg_b)) => {
+            alpha_eq_in(func_a, func_b, bound) && alpha_eq_in(arg_a, arg_b, bound)
+        }
+        _ => false,
+    }
+}
+
+fn rename_in(term: &Term, from: &str, to: &str) -> Term {
+    if term.has_free(from) {
+        Term::new(term.rename_var(from, to))
+    } else {
+        term.clone()
+    }
+}
+
+fn descend<'a>(
+    highlights: &[(&'a [Branch], &'a str)],
+    branch: Branch,
+) -> Vec<(&'a [Branch], &'a str)> {
+    highlights
+        .iter()
+        .filter_map(|(path, code)| match path.split_first() {
+            Some((first, rest)) if *first == branch => Some((rest, *code)),
+            _ => None,
+        })
+        .collect()
 }
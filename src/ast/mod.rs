@@ -1,5 +1,15 @@
+mod de_bruijn;
 mod expr;
+mod path;
 mod red_type;
+mod strategy;
+mod term;
+mod typing;
 
-pub use expr::{abs, apl, var, Expr};
+pub use de_bruijn::DeBruijn;
+pub use expr::{abs, apl, var, Definitions, Expr};
+pub use path::{Branch, Path};
 pub use red_type::RedType;
+pub use strategy::Strategy;
+pub use term::Term;
+pub use typing::Type;
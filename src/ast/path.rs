@@ -0,0 +1,15 @@
+// One step from a node to one of its children
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Branch {
+    // Body of an abstraction
+    Body,
+
+    // Function side of an application
+    Func,
+
+    // Argument side of an application
+    Arg,
+}
+
+// Position of a subterm, as the branches taken from the root
+pub type Path = Vec<Branch>;
@@ -1,3 +1,5 @@
+use super::Branch;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RedType {
     // (λx.M) N → M[x := N]
@@ -6,6 +8,9 @@ pub enum RedType {
     // λx.M → λy.M[x := y] where y is not free in M
     AlphaConversion(String, String), // from, to
 
+    // name → its definition
+    DeltaReduction(String),
+
     // Reduction inside a subexpression
     ContextualReduction(String),
 
@@ -24,6 +29,7 @@ impl RedType {
         let alpha = if utf8 { "α" } else { "A" };
         let beta = if utf8 { "β" } else { "B" };
         let gamma = if utf8 { "γ" } else { "C" };
+        let delta = if utf8 { "δ" } else { "D" };
 
         match self {
             RedType::AlphaConversion(from, to) => {
@@ -38,6 +44,12 @@ impl RedType {
                     gray, type_, beta, gray, reset, var, gray, reset
                 )
             }
+            RedType::DeltaReduction(name) => {
+                format!(
+                    "{}->{}{}{}({}{}{}){}",
+                    gray, type_, delta, gray, reset, name, gray, reset
+                )
+            }
             RedType::ContextualReduction(var) => {
                 format!(
                     "{}->{}{}{}({}{}{}){}",
@@ -54,3 +66,53 @@ impl RedType {
         }
     }
 }
+
+impl RedType {
+    // Reductions inside an application are reported as contextual, on the side the path
+    // enters it first, unfoldings keep their name so callers can tell which definition
+    // was used
+    pub(crate) fn in_context_of(self, path: &[Branch]) -> RedType {
+        let side = path.iter().find_map(|branch| match branch {
+            Branch::Func => Some("l"),
+            Branch::Arg => Some("r"),
+            Branch::Body => None,
+        });
+        match (self, side) {
+            (RedType::DeltaReduction(name), _) => RedType::DeltaReduction(name),
+            (red, None) => red,
+            (_, Some(side)) => RedType::ContextualReduction(side.to_string()),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RedType::BetaReduction(_) => "beta",
+            RedType::AlphaConversion(_, _) => "alpha",
+            RedType::DeltaReduction(_) => "delta",
+            RedType::ContextualReduction(_) => "contextual",
+            RedType::Simplification(_) => "simplification",
+            RedType::NoReduction => "none",
+        }
+    }
+
+    pub fn fmt_latex(&self) -> String {
+        match self {
+            RedType::BetaReduction(_) => "\\to_\\beta".into(),
+            RedType::AlphaConversion(_, _) | RedType::Simplification(_) => "\\equiv_\\alpha".into(),
+            RedType::DeltaReduction(_) => "\\to_\\delta".into(),
+            // Says nothing of the rule, `Step::kind` has it
+            RedType::ContextualReduction(_) => "\\to".into(),
+            RedType::NoReduction => "=".into(),
+        }
+    }
+
+    pub fn fmt_unicode(&self) -> String {
+        match self {
+            RedType::BetaReduction(_) => "→β".into(),
+            RedType::AlphaConversion(_, _) | RedType::Simplification(_) => "≡α".into(),
+            RedType::DeltaReduction(_) => "→δ".into(),
+            RedType::ContextualReduction(_) => "→".into(),
+            RedType::NoReduction => "=".into(),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    // Leftmost-outermost redex first, finds a normal form whenever one exists
+    #[default]
+    NormalOrder,
+
+    // Leftmost-innermost redex first, arguments are reduced before being substituted
+    ApplicativeOrder,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 2] = [Strategy::NormalOrder, Strategy::ApplicativeOrder];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Strategy::NormalOrder => "normal",
+            Strategy::ApplicativeOrder => "applicative",
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" | "normal-order" => Ok(Strategy::NormalOrder),
+            "applicative" | "applicative-order" => Ok(Strategy::ApplicativeOrder),
+            _ => Err(format!(
+                "unknown strategy '{}', expected one of: {}",
+                s,
+                Strategy::ALL.map(|s| s.name()).join(", ")
+            )),
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use super::Expr;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    hash::{BuildHasherDefault, Hash, Hasher},
+    ops::Deref,
+    sync::{Arc, Weak},
+};
+
+/// A shared, hash-consed subterm. Equal terms built on the same thread from the same
+/// children are the same node while the thread's table of nodes has room, and each node
+/// caches its free variables, size, depth and whether it is in normal form, so asking
+/// any of them does not walk the term, and cloning it does not copy the term.
+#[derive(Clone)]
+pub struct Term(Arc<Node>);
+
+struct Node {
+    expr: Expr,
+    // Structural, children contribute their own hashes
+    hash: u64,
+    // Sorted and deduplicated, shared with a child when they are the same
+    free: Arc<[Arc<str>]>,
+    normal: bool,
+    size: usize,
+    depth: usize,
+}
+
+// Live nodes by hash, each thread has its own so building terms never waits on a lock.
+// Two different terms with the same hash only cost sharing, the later one is left out.
+// Nodes dropped on another thread stay in the table until swept or replaced
+#[derive(Default)]
+struct Table {
+    nodes: HashMap<u64, Weak<Node>, BuildHasherDefault<HashHasher>>,
+    // Nodes left out of the full table since it was last swept
+    missed: usize,
+}
+
+thread_local! {
+    static TABLE: RefCell<Table> = RefCell::default();
+}
+
+// Past this many nodes new ones are left unshared. Lookups in a table much larger than
+// the caches cost more than the sharing saves, as measured by `benches/reduction.rs`
+const MAX_SHARED: usize = 1 << 16;
+
+impl Table {
+    // Makes room once enough nodes were left out to pay for a sweep
+    fn is_full(&mut self) -> bool {
+        if self.nodes.len() < MAX_SHARED {
+            return false;
+        }
+        self.missed += 1;
+        if self.missed >= MAX_SHARED / 4 {
+            self.missed = 0;
+            self.nodes.retain(|_, node| node.strong_count() > 0);
+        }
+        self.nodes.len() >= MAX_SHARED
+    }
+}
+
+// The keys are hashes already
+#[derive(Default)]
+struct HashHasher(u64);
+
+impl Hasher for HashHasher {
+    fn write(&mut self, _: &[u8]) {
+        unreachable!("only u64 keys are hashed");
+    }
+
+    fn write_u64(&mut self, hash: u64) {
+        self.0 = hash;
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Term {
+    /// The shared node for `expr`, created the first time it is seen on this thread.
+    pub fn new(expr: Expr) -> Term {
+        let hash = hash_of(&expr);
+        let shared = TABLE.try_with(|table| {
+            let table = &mut *table.borrow_mut();
+            match table.nodes.get(&hash).and_then(Weak::upgrade) {
+                Some(node) if same_node(&node.expr, &expr) => Ok(Term(node)),
+                // A different term with the same hash keeps its place
+                Some(_) => Err(false),
+                None => Err(!table.is_full()),
+            }
+        });
+        let share = match shared {
+            Ok(Ok(term)) => return term,
+            Ok(Err(share)) => share,
+            // The thread is ending
+            Err(_) => false,
+        };
+        let node = Arc::new(Node::new(expr, hash));
+        if share {
+            TABLE.with_borrow_mut(|table| table.nodes.insert(hash, Arc::downgrade(&node)));
+        }
+        Term(node)
+    }
+
+    /// Free variables, sorted.
+    pub fn free(&self) -> &[Arc<str>] {
+        &self.0.free
+    }
+
+    pub fn has_free(&self, var: &str) -> bool {
+        self.0
+            .free
+            .binary_search_by(|name| (**name).cmp(var))
+            .is_ok()
+    }
+
+    pub fn is_normal_form(&self) -> bool {
+        self.0.normal
+    }
+
+    pub fn size(&self) -> usize {
+        self.0.size
+    }
+
+    pub fn depth(&self) -> usize {
+        self.0.depth
+    }
+}
+
+impl Node {
+    fn new(expr: Expr, hash: u64) -> Self {
+        Node {
+            hash,
+            free: free_of(&expr),
+            normal: expr.is_normal_form(),
+            size: expr.size(),
+            depth: expr.depth(),
+            expr,
+        }
+    }
+}
+
+// Leaves the table with the node when dropped on the thread that built it, unless the
+// entry is a newer node with the same hash
+impl Drop for Node {
+    fn drop(&mut self) {
+        let _ = TABLE.try_with(|table| {
+            // Taken when dropping a node `Term::new` looked up
+            let Ok(mut table) = table.try_borrow_mut() else {
+                return;
+            };
+            if table
+                .nodes
+                .get(&self.hash)
+                .is_some_and(|node| std::ptr::eq(node.as_ptr(), self))
+            {
+                table.nodes.remove(&self.hash);
+            }
+        });
+    }
+}
+
+// Same name and the very same children
+fn same_node(a: &Expr, b: &Expr) -> bool {
+    match (a, b) {
+        (Expr::Var(a), Expr::Var(b)) => a == b,
+        (Expr::Abs(param_a, body_a), Expr::Abs(param_b, body_b)) => {
+            param_a == param_b && Arc::ptr_eq(&body_a.0, &body_b.0)
+        }
+        (Expr::Apl(func_a, arg_a), Expr::Apl(func_b, arg_b)) => {
+            Arc::ptr_eq(&func_a.0, &func_b.0) && Arc::ptr_eq(&arg_a.0, &arg_b.0)
+        }
+        _ => false,
+    }
+}
+
+fn hash_of(expr: &Expr) -> u64 {
+    match expr {
+        Expr::Var(name) => hash_name(1, name),
+        Expr::Abs(param, body) => mix(hash_name(2, param), body.0.hash),
+        Expr::Apl(e1, e2) => mix(mix(3, e1.0.hash), e2.0.hash),
+    }
+}
+
+fn hash_name(seed: u64, name: &str) -> u64 {
+    name.as_bytes().chunks(8).fold(seed, |hash, chunk| {
+        let mut word = [0; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        mix(hash, u64::from_le_bytes(word))
+    })
+}
+
+// The splitmix64 finalizer over both words
+fn mix(hash: u64, word: u64) -> u64 {
+    let mut x = hash.rotate_left(32) ^ word.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+// Free variables of a node from the cached sets of its children, the names themselves
+// are shared too
+fn free_of(expr: &Expr) -> Arc<[Arc<str>]> {
+    match expr {
+        Expr::Var(name) => Arc::new([name.as_str().into()]),
+        Expr::Abs(param, body) if !body.has_free(param) => body.0.free.clone(),
+        Expr::Abs(param, body) => body
+            .free()
+            .iter()
+            .filter(|name| ***name != **param)
+            .cloned()
+            .collect(),
+        Expr::Apl(e1, e2) if is_subset(e2.free(), e1.free()) => e1.0.free.clone(),
+        Expr::Apl(e1, e2) if is_subset(e1.free(), e2.free()) => e2.0.free.clone(),
+        Expr::Apl(e1, e2) => {
+            let mut free = Vec::with_capacity(e1.free().len() + e2.free().len());
+            let (mut left, mut right) = (e1.free(), e2.free());
+            while let (Some(a), Some(b)) = (left.first(), right.first()) {
+                if a <= b {
+                    free.push(a.clone());
+                    left = &left[1..];
+                    right = if a == b { &right[1..] } else { right };
+                } else {
+                    free.push(b.clone());
+                    right = &right[1..];
+                }
+            }
+            free.extend_from_slice(left);
+            free.extend_from_slice(right);
+            free.into()
+        }
+    }
+}
+
+// Both sorted
+fn is_subset(small: &[Arc<str>], large: &[Arc<str>]) -> bool {
+    let mut large = large.iter();
+    small.iter().all(|name| large.any(|other| other == name))
+}
+
+impl Deref for Term {
+    type Target = Expr;
+
+    fn deref(&self) -> &Expr {
+        &self.0.expr
+    }
+}
+
+impl From<Expr> for Term {
+    fn from(expr: Expr) -> Self {
+        Term::new(expr)
+    }
+}
+
+impl fmt::Debug for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.expr.fmt(f)
+    }
+}
+
+// Shared nodes are equal without looking further, others when their hashes and shapes agree
+impl PartialEq for Term {
+    fn eq(&self, other: &Self) -> bool {
+        if Arc::ptr_eq(&self.0, &other.0) {
+            return true;
+        }
+        self.0.hash == other.0.hash
+            && match (&self.0.expr, &other.0.expr) {
+                (Expr::Var(a), Expr::Var(b)) => a == b,
+                (Expr::Abs(param_a, body_a), Expr::Abs(param_b, body_b)) => {
+                    param_a == param_b && body_a == body_b
+                }
+                (Expr::Apl(func_a, arg_a), Expr::Apl(func_b, arg_b)) => {
+                    func_a == func_b && arg_a == arg_b
+                }
+                _ => false,
+            }
+    }
+}
+
+impl Eq for Term {}
+
+impl Hash for Term {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str) -> Term {
+        Term::new(Expr::Var(name.to_string()))
+    }
+
+    fn apl(e1: &Term, e2: &Term) -> Term {
+        Term::new(Expr::Apl(e1.clone(), e2.clone()))
+    }
+
+    fn is_shared(hash: u64) -> bool {
+        TABLE.with_borrow(|table| table.nodes.contains_key(&hash))
+    }
+
+    #[test]
+    fn equal_terms_are_the_same_node() {
+        let (x, y) = (var("x"), var("y"));
+        let a = apl(&x, &y);
+        let b = apl(&var("x"), &var("y"));
+        assert!(Arc::ptr_eq(&a.0, &b.0));
+        assert!(!Arc::ptr_eq(&a.0, &apl(&y, &x).0));
+        assert_eq!(a.free(), [Arc::from("x"), Arc::from("y")]);
+    }
+
+    #[test]
+    fn dropped_nodes_leave_the_table() {
+        let node = apl(&var("dropped"), &var("node"));
+        let hash = node.0.hash;
+        assert!(is_shared(hash));
+        drop(node);
+        assert!(!is_shared(hash));
+    }
+
+    #[test]
+    fn threads_share_their_own_nodes() {
+        let here = apl(&var("thread"), &var("local"));
+        let there = std::thread::spawn(|| apl(&var("thread"), &var("local")))
+            .join()
+            .unwrap();
+        assert!(!Arc::ptr_eq(&here.0, &there.0));
+        assert_eq!(here, there);
+
+        // Dropping the other thread's node leaves this table alone
+        let hash = there.0.hash;
+        drop(there);
+        let entry = TABLE.with_borrow(|table| table.nodes[&hash].as_ptr());
+        assert!(std::ptr::eq(entry, Arc::as_ptr(&here.0)));
+    }
+}
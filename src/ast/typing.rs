@@ -0,0 +1,167 @@
+use super::{Definitions, Expr};
+use std::{collections::HashMap, fmt};
+
+/// A simple type: type variables and function arrows.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    Var(usize),
+    Arrow(Box<Type>, Box<Type>),
+}
+
+impl Type {
+    pub fn fmt_with_config(&self, utf8: bool) -> String {
+        if utf8 {
+            self.to_string()
+        } else {
+            self.to_string().replace('→', "->")
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Var(i) if *i < 26 => write!(f, "{}", (b'a' + *i as u8) as char),
+            Type::Var(i) => write!(f, "t{}", i),
+            Type::Arrow(from, to) => match **from {
+                Type::Arrow(..) => write!(f, "({}) → {}", from, to),
+                Type::Var(_) => write!(f, "{} → {}", from, to),
+            },
+        }
+    }
+}
+
+impl Expr {
+    /// Infers the principal simple type of the term, unfolding defined names. Each use of
+    /// a definition is typed on its own, so `id id` is typable when `id` is defined.
+    /// Terms such as `λx.x x` have no simple type and give an error.
+    pub fn infer_type(&self, definitions: &Definitions) -> Result<Type, String> {
+        let mut inference = Inference {
+            definitions,
+            substitution: Vec::new(),
+            free: HashMap::new(),
+            unfolding: Vec::new(),
+        };
+        let ty = inference.infer(self, &mut Vec::new())?;
+        let ty = inference.resolve(&ty);
+        Ok(renumber(&ty, &mut HashMap::new()))
+    }
+}
+
+struct Inference<'a> {
+    definitions: &'a Definitions,
+    // Type bound to each type variable so far
+    substitution: Vec<Option<Type>>,
+    // Free names that are not defined share one type variable per name
+    free: HashMap<String, Type>,
+    // Definitions being typed, to reject recursive ones
+    unfolding: Vec<String>,
+}
+
+impl Inference<'_> {
+    fn fresh(&mut self) -> Type {
+        self.substitution.push(None);
+        Type::Var(self.substitution.len() - 1)
+    }
+
+    fn infer(&mut self, expr: &Expr, env: &mut Vec<(String, Type)>) -> Result<Type, String> {
+        match expr {
+            Expr::Var(name) => {
+                if let Some((_, ty)) = env.iter().rev().find(|(bound, _)| bound == name) {
+                    return Ok(ty.clone());
+                }
+                if let Some(definition) = self.definitions.get(name) {
+                    if self.unfolding.contains(name) {
+                        return Err(format!("{} is defined recursively", name));
+                    }
+                    self.unfolding.push(name.clone());
+                    let ty = self.infer(definition, &mut Vec::new());
+                    self.unfolding.pop();
+                    return ty;
+                }
+                if let Some(ty) = self.free.get(name) {
+                    return Ok(ty.clone());
+                }
+                let ty = self.fresh();
+                self.free.insert(name.clone(), ty.clone());
+                Ok(ty)
+            }
+            Expr::Abs(param, body) => {
+                let param_ty = self.fresh();
+                env.push((param.clone(), param_ty.clone()));
+                let body_ty = self.infer(body, env);
+                env.pop();
+                Ok(Type::Arrow(Box::new(param_ty), Box::new(body_ty?)))
+            }
+            Expr::Apl(func, arg) => {
+                let func_ty = self.infer(func, env)?;
+                let arg_ty = self.infer(arg, env)?;
+                let result = self.fresh();
+                let expected = Type::Arrow(Box::new(arg_ty), Box::new(result.clone()));
+                // Only the occurs check can fail with nothing but arrows
+                self.unify(&func_ty, &expected).map_err(|_| {
+                    "The term has no simple type, it would need an infinite one".to_string()
+                })?;
+                Ok(result)
+            }
+        }
+    }
+
+    // Follows bound type variables until an unbound one or an arrow
+    fn walk(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(i) => match &self.substitution[*i] {
+                Some(bound) => self.walk(bound),
+                None => ty.clone(),
+            },
+            Type::Arrow(..) => ty.clone(),
+        }
+    }
+
+    fn resolve(&self, ty: &Type) -> Type {
+        match self.walk(ty) {
+            Type::Arrow(from, to) => {
+                Type::Arrow(Box::new(self.resolve(&from)), Box::new(self.resolve(&to)))
+            }
+            var => var,
+        }
+    }
+
+    fn occurs(&self, var: usize, ty: &Type) -> bool {
+        match self.walk(ty) {
+            Type::Var(i) => i == var,
+            Type::Arrow(from, to) => self.occurs(var, &from) || self.occurs(var, &to),
+        }
+    }
+
+    fn unify(&mut self, a: &Type, b: &Type) -> Result<(), ()> {
+        match (self.walk(a), self.walk(b)) {
+            (Type::Var(i), Type::Var(j)) if i == j => Ok(()),
+            (Type::Var(i), ty) | (ty, Type::Var(i)) => {
+                if self.occurs(i, &ty) {
+                    return Err(());
+                }
+                self.substitution[i] = Some(ty);
+                Ok(())
+            }
+            (Type::Arrow(from_a, to_a), Type::Arrow(from_b, to_b)) => {
+                self.unify(&from_a, &from_b)?;
+                self.unify(&to_a, &to_b)
+            }
+        }
+    }
+}
+
+// Numbers type variables in order of appearance, so types read `a → b → a`
+fn renumber(ty: &Type, names: &mut HashMap<usize, usize>) -> Type {
+    match ty {
+        Type::Var(i) => {
+            let next = names.len();
+            Type::Var(*names.entry(*i).or_insert(next))
+        }
+        Type::Arrow(from, to) => {
+            let from = renumber(from, names);
+            Type::Arrow(Box::new(from), Box::new(renumber(to, names)))
+        }
+    }
+}
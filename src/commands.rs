@@ -0,0 +1,876 @@
+use crate::{
+    config::{self, SETTINGS},
+    debugger::{Breakpoint, Debugger},
+    state::{Report, State},
+};
+use anyhow::{anyhow, Result};
+use rambda::{
+    ast::{Expr, Strategy},
+    render,
+    vm::{MachineRun, MachineState, Termination, Vm},
+};
+use std::{fmt, sync::mpsc};
+
+/// A command of the REPL, TUI and scripts, written `:name args`. The commands that
+/// predate the colon, `eval`, `clear` and `exit` or `quit`, still run without it, see
+/// `find_bare`.
+pub struct Command {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    // Kept in the session, so saving it replays the command
+    pub record: bool,
+    // Arguments, as shown in the usage
+    pub args: &'static str,
+    pub about: &'static str,
+    run: fn(&mut State, &[String]) -> Result<()>,
+}
+
+impl Command {
+    pub fn usage(&self) -> String {
+        format!(":{} {}", self.name, self.args)
+            .trim_end()
+            .to_string()
+    }
+
+    /// Runs the command, reporting errors and misuse in the history. Returns whether it
+    /// succeeded.
+    pub fn exec(&self, state: &mut State, args: &[String]) -> bool {
+        match (self.run)(state, args) {
+            Ok(()) => return true,
+            Err(err) if err.is::<UsageError>() => {
+                state.history.push(format!("Usage: {}", self.usage()));
+            }
+            Err(err) => state.history.push(format!("Error: {}", err)),
+        }
+        false
+    }
+}
+
+// Returned by commands given the wrong arguments, reported with the command's usage
+#[derive(Debug)]
+struct UsageError;
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid arguments")
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+// Nested `:load`s before giving up, a script loading itself would never end
+const MAX_SCRIPT_DEPTH: usize = 16;
+const SESSION_HEADER: &str = "# rambda session, replay it with :restore\n";
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "help",
+        aliases: &["?"],
+        record: false,
+        args: "[command]",
+        about: "List the commands, or show how to use one",
+        run: help,
+    },
+    Command {
+        name: "eval",
+        aliases: &[],
+        record: true,
+        args: "<expr>",
+        about: "Reduce an expression to normal form, printing every step",
+        run: eval,
+    },
+    Command {
+        name: "steps",
+        aliases: &[],
+        record: true,
+        args: "<expr>",
+        about: "Reduce an expression, printing only the result and the step count",
+        run: steps,
+    },
+    Command {
+        name: "stats",
+        aliases: &[],
+        record: true,
+        args: "[expr]",
+        about: "Reduce an expression and print statistics, or show the ones of the last evaluation",
+        run: stats,
+    },
+    Command {
+        name: "need",
+        aliases: &[],
+        record: true,
+        args: "<expr>",
+        about: "Reduce an expression by need, sharing arguments, and count the steps saved",
+        run: need,
+    },
+    Command {
+        name: "krivine",
+        aliases: &[],
+        record: true,
+        args: "<expr>",
+        about: "Evaluate a closed expression to weak head normal form on the Krivine machine, showing its states",
+        run: krivine,
+    },
+    Command {
+        name: "cek",
+        aliases: &[],
+        record: true,
+        args: "<expr>",
+        about: "Evaluate a closed expression by value on the CEK machine, showing its states",
+        run: cek,
+    },
+    Command {
+        name: "bytecode",
+        aliases: &[],
+        record: true,
+        args: "<expr>",
+        about: "Compile a closed expression to bytecode and print its disassembly",
+        run: bytecode,
+    },
+    Command {
+        name: "type",
+        aliases: &[],
+        record: true,
+        args: "<expr>",
+        about: "Infer the simple type of an expression",
+        run: type_of,
+    },
+    Command {
+        name: "tree",
+        aliases: &[],
+        record: true,
+        args: "[--dot] [--binders] <expr>",
+        about: "Draw the syntax tree, or print it as Graphviz DOT",
+        run: tree,
+    },
+    Command {
+        name: "diagram",
+        aliases: &[],
+        record: true,
+        args: "[--steps] [--svg <path>] <expr>",
+        about: "Draw the Tromp diagram of an expression or of every step",
+        run: diagram,
+    },
+    Command {
+        name: "export",
+        aliases: &[],
+        record: true,
+        args: "<latex|markdown> <expr>",
+        about: "Print the reduction of an expression as LaTeX or Markdown",
+        run: export,
+    },
+    Command {
+        name: "debug",
+        aliases: &[],
+        record: false,
+        args: "<expr>",
+        about: "Start stepping through the reduction of an expression",
+        run: debug,
+    },
+    Command {
+        name: "step",
+        aliases: &[],
+        record: false,
+        args: "[count]",
+        about: "Take reduction steps in the debugger",
+        run: step,
+    },
+    Command {
+        name: "back",
+        aliases: &[],
+        record: false,
+        args: "[count]",
+        about: "Go back reduction steps in the debugger",
+        run: back,
+    },
+    Command {
+        name: "run",
+        aliases: &[],
+        record: false,
+        args: "",
+        about: "Reduce until normal form or a breakpoint in the debugger",
+        run: run_debugger,
+    },
+    Command {
+        name: "break",
+        aliases: &[],
+        record: false,
+        args: "[unfold <name> | size <nodes>]",
+        about: "Set a breakpoint in the debugger, or list them",
+        run: set_breakpoint,
+    },
+    Command {
+        name: "unbreak",
+        aliases: &[],
+        record: false,
+        args: "[index]",
+        about: "Remove a breakpoint, or all of them",
+        run: unbreak,
+    },
+    Command {
+        name: "close",
+        aliases: &[],
+        record: false,
+        args: "",
+        about: "Close the debugger",
+        run: close,
+    },
+    Command {
+        name: "strategy",
+        aliases: &[],
+        record: true,
+        args: "[normal|applicative]",
+        about: "Show or change the reduction strategy",
+        run: strategy,
+    },
+    Command {
+        name: "set",
+        aliases: &[],
+        record: true,
+        args: "<key> [value]",
+        about: "Show or change a setting for this session",
+        run: set,
+    },
+    Command {
+        name: "config",
+        aliases: &[],
+        record: false,
+        args: "",
+        about: "Show every setting and where its value comes from",
+        run: show_config,
+    },
+    Command {
+        name: "save-config",
+        aliases: &[],
+        record: false,
+        args: "[path]",
+        about: "Write the settings to the config file, or to another file",
+        run: save_config,
+    },
+    Command {
+        name: "defs",
+        aliases: &[],
+        record: false,
+        args: "",
+        about: "List the defined names",
+        run: defs,
+    },
+    Command {
+        name: "load",
+        aliases: &[],
+        record: true,
+        args: "<path>",
+        about: "Run every line of a script",
+        run: load,
+    },
+    Command {
+        name: "save",
+        aliases: &[],
+        record: false,
+        args: "<path>",
+        about: "Write the definitions and evaluated commands to a replayable script",
+        run: save,
+    },
+    Command {
+        name: "restore",
+        aliases: &[],
+        record: false,
+        args: "<path>",
+        about: "Start over from a saved session",
+        run: restore,
+    },
+    Command {
+        name: "clear",
+        aliases: &[],
+        record: false,
+        args: "",
+        about: "Clear the history",
+        run: clear,
+    },
+    Command {
+        name: "exit",
+        aliases: &["quit"],
+        record: false,
+        args: "",
+        about: "Leave rambda",
+        run: exit,
+    },
+];
+
+/// The command called `name` or one of its aliases.
+pub fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS
+        .iter()
+        .find(|command| command.name == name || command.aliases.contains(&name))
+}
+
+/// The command a first word without a colon runs: one of the commands that predate the
+/// colon, unless a definition has its name and the word starts an expression.
+pub fn find_bare(word: &str, is_defined: impl Fn(&str) -> bool) -> Option<&'static Command> {
+    ["eval", "clear", "exit", "quit"]
+        .contains(&word)
+        .then(|| find(word))
+        .flatten()
+        .filter(|_| !is_defined(word))
+}
+
+fn help(state: &mut State, args: &[String]) -> Result<()> {
+    if let Some(name) = args.first() {
+        let command = find(name.trim_start_matches(':'))
+            .ok_or_else(|| anyhow!("Unknown command: {}", name))?;
+        state.history.push(command.usage());
+        state.history.push(format!("  {}", command.about));
+        return Ok(());
+    }
+
+    let width = COMMANDS
+        .iter()
+        .map(|command| command.usage().chars().count())
+        .max()
+        .unwrap_or_default();
+    state.history.push("Commands:".to_string());
+    for command in COMMANDS {
+        state.history.push(format!(
+            "  {:width$}  {}",
+            command.usage(),
+            command.about,
+            width = width
+        ));
+    }
+    state
+        .history
+        .push("Anything else is evaluated, and `name := <expr>` defines a name.".to_string());
+    Ok(())
+}
+
+fn eval(state: &mut State, args: &[String]) -> Result<()> {
+    let input = parse(state, args)?;
+    state.start_eval(input, Report::Trace);
+    Ok(())
+}
+
+fn steps(state: &mut State, args: &[String]) -> Result<()> {
+    let input = parse(state, args)?;
+    state.start_eval(input, Report::Count);
+    Ok(())
+}
+
+fn stats(state: &mut State, args: &[String]) -> Result<()> {
+    if !args.is_empty() {
+        let input = parse(state, args)?;
+        state.start_eval(input, Report::Stats);
+        return Ok(());
+    }
+    let stats = state
+        .last_stats
+        .as_ref()
+        .ok_or_else(|| anyhow!("Nothing was evaluated yet"))?;
+    let lines = stats.to_string();
+    state.history.extend(lines.lines().map(String::from));
+    Ok(())
+}
+
+fn need(state: &mut State, args: &[String]) -> Result<()> {
+    let expr = parse(state, args)?;
+    let (color, utf8, merge) = (
+        state.config.use_color,
+        state.config.use_utf8,
+        state.config.merge_args,
+    );
+    state.start_job(
+        expr.clone(),
+        Box::new(move |vm, print| {
+            let by_need = vm.evaluate_by_need(&expr)?;
+            let steps = by_need.steps();
+            print(match (&by_need.result, by_need.termination) {
+                (Some(result), _) => format!(
+                    "=> {} ({} steps by need)",
+                    result.fmt_with_config(color, utf8, merge),
+                    steps
+                ),
+                (None, Termination::Cancelled) => {
+                    format!("Cancelled after {} steps by need", steps)
+                }
+                (None, Termination::DepthLimit) => format!(
+                    "The normal form grew too deep after {} steps by need",
+                    steps
+                ),
+                (None, _) => format!("No normal form within {} steps by need", steps),
+            });
+            if by_need.termination == Termination::Cancelled {
+                return Ok(());
+            }
+
+            // The same term without sharing, for comparison, keeping only its statistics
+            let mut naive = vm;
+            naive.set_strategy(Strategy::NormalOrder);
+            naive.set_expr(expr);
+            let mut iter = naive.steps();
+            iter.by_ref().for_each(drop);
+            let termination = iter.termination().unwrap_or(Termination::NormalForm);
+            let stats = naive.stats();
+            let naive_steps = stats.beta + stats.delta;
+            print(match termination {
+                Termination::NormalForm => match by_need.saved(stats) {
+                    saved if saved >= 0 => format!(
+                        "  normal order: {} steps, sharing saved {}",
+                        naive_steps, saved
+                    ),
+                    saved => format!(
+                        "  normal order: {} steps, sharing took {} more",
+                        naive_steps, -saved
+                    ),
+                },
+                Termination::Cancelled => {
+                    format!("  normal order: cancelled after {} steps", naive_steps)
+                }
+                Termination::SizeLimit | Termination::DepthLimit => format!(
+                    "  normal order: the term grew too large after {} steps",
+                    naive_steps
+                ),
+                _ => format!(
+                    "  normal order: no normal form within {} steps",
+                    naive_steps
+                ),
+            });
+            Ok(())
+        }),
+    );
+    Ok(())
+}
+
+fn krivine(state: &mut State, args: &[String]) -> Result<()> {
+    let expr = parse(state, args)?;
+    start_machine(state, expr, |vm, expr, observe| {
+        vm.evaluate_krivine_with(expr, observe)
+    });
+    Ok(())
+}
+
+fn cek(state: &mut State, args: &[String]) -> Result<()> {
+    let expr = parse(state, args)?;
+    start_machine(state, expr, |vm, expr, observe| {
+        vm.evaluate_cek_with(expr, observe)
+    });
+    Ok(())
+}
+
+fn bytecode(state: &mut State, args: &[String]) -> Result<()> {
+    let expr = parse(state, args)?;
+    let program = state.vm.compile(&expr)?;
+    state
+        .history
+        .extend(program.to_string().lines().map(String::from));
+    Ok(())
+}
+
+// A machine run with the definitions and limits of a vm, handing it each state
+type RunMachine = fn(&Vm, &Expr, &mut dyn FnMut(&MachineState)) -> Result<MachineRun>;
+
+// Runs a machine on the worker thread, printing its states as it reaches them
+fn start_machine(state: &mut State, expr: Expr, run: RunMachine) {
+    let config = &state.config;
+    let (color, utf8, merge) = (config.use_color, config.use_utf8, config.merge_args);
+    state.start_job(
+        expr.clone(),
+        Box::new(move |vm, print| {
+            let run = run(&vm, &expr, &mut |machine_state| {
+                print(fmt_machine_state(machine_state, color, utf8, merge))
+            })?;
+            print(fmt_machine_result(&run, color, utf8, merge));
+            Ok(())
+        }),
+    );
+}
+
+fn fmt_machine_state(machine_state: &MachineState, color: bool, utf8: bool, merge: bool) -> String {
+    let fmt_state = machine_state.fmt_with_config(color, utf8, merge);
+    match &machine_state.transition {
+        Some(transition) => format!("  {} {}", transition.fmt_with_config(color), fmt_state),
+        None => format!("  {}", fmt_state),
+    }
+}
+
+fn fmt_machine_result(run: &MachineRun, color: bool, utf8: bool, merge: bool) -> String {
+    let steps = run.steps();
+    match (&run.result, run.termination) {
+        (Some(result), _) => format!(
+            "=> {} ({} steps)",
+            result.fmt_with_config(color, utf8, merge),
+            steps
+        ),
+        (None, Termination::Cancelled) => format!("Cancelled after {} steps", steps),
+        (None, _) => format!("No value within {} steps", steps),
+    }
+}
+
+fn type_of(state: &mut State, args: &[String]) -> Result<()> {
+    let expr = parse(state, args)?;
+    let ty = expr
+        .infer_type(state.vm.definitions())
+        .map_err(|err| anyhow!(err))?;
+    state.history.push(format!(
+        "{} : {}",
+        state.fmt_expr(&expr),
+        ty.fmt_with_config(state.config.use_utf8)
+    ));
+    Ok(())
+}
+
+fn tree(state: &mut State, args: &[String]) -> Result<()> {
+    let dot = args.iter().any(|arg| arg == "--dot");
+    let binders = args.iter().any(|arg| arg == "--binders");
+    let expr = args
+        .iter()
+        .filter(|arg| !arg.starts_with("--"))
+        .cloned()
+        .collect::<Vec<_>>();
+    let expr = parse(state, &expr)?;
+    let rendered = if dot {
+        render::expr_to_dot(&expr, state.vm.strategy(), binders)
+    } else {
+        render::expr_to_ascii_tree(&expr, state.vm.strategy(), state.config.use_utf8)
+    };
+    state.history.extend(rendered.lines().map(String::from));
+    state.history.push(String::new());
+    Ok(())
+}
+
+fn diagram(state: &mut State, args: &[String]) -> Result<()> {
+    let mut steps = false;
+    let mut svg = None;
+    let mut rest = args.iter();
+    let mut expr = Vec::new();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--steps" => steps = true,
+            "--svg" => svg = Some(rest.next().ok_or(UsageError)?.clone()),
+            _ => expr.push(arg.clone()),
+        }
+    }
+
+    let input = parse(state, &expr)?;
+    if !steps {
+        return show_diagrams(state, vec![input], svg, false);
+    }
+    state.start_collect(
+        input.clone(),
+        Box::new(move |state, trace| {
+            let mut frames = vec![input];
+            frames.extend(trace.into_iter().map(|step| step.expr));
+            show_diagrams(state, frames, svg, true)
+        }),
+    );
+    Ok(())
+}
+
+// Writes the diagrams of `frames` to `svg`, or shows them numbered when they are `steps`
+fn show_diagrams(
+    state: &mut State,
+    frames: Vec<Expr>,
+    svg: Option<String>,
+    steps: bool,
+) -> Result<()> {
+    if let Some(path) = svg {
+        return state.write_diagrams(&path, &frames);
+    }
+    for (i, frame) in frames.iter().enumerate() {
+        if steps {
+            state
+                .history
+                .push(format!("[{}] {}", i, state.fmt_expr(frame)));
+        }
+        let diagram = render::expr_to_diagram(frame, state.config.use_utf8);
+        state.history.extend(diagram.lines().map(String::from));
+        state.history.push(String::new());
+    }
+    Ok(())
+}
+
+fn export(state: &mut State, args: &[String]) -> Result<()> {
+    let format = args.first().map(String::as_str).unwrap_or_default();
+    if !matches!(format, "latex" | "markdown") {
+        return Err(UsageError.into());
+    }
+    let latex = format == "latex";
+    let input = parse(state, &args[1..])?;
+    state.start_collect(
+        input.clone(),
+        Box::new(move |state, steps| {
+            let rendered = if latex {
+                render::trace_to_latex(&input, &steps)
+            } else {
+                render::trace_to_markdown(&input, &steps)
+            };
+            state.history.extend(rendered.lines().map(String::from));
+            state.history.push(String::new());
+            Ok(())
+        }),
+    );
+    Ok(())
+}
+
+fn debug(state: &mut State, args: &[String]) -> Result<()> {
+    let expr = parse(state, args)?;
+    state.debugger = Some(Debugger::new(expr, &mut state.vm)?);
+    state.push_debugger_status();
+    Ok(())
+}
+
+fn step(state: &mut State, args: &[String]) -> Result<()> {
+    let count = count(args)?;
+    start_debugger(state, move |debugger, vm| debugger.forward(vm, count))
+}
+
+fn back(state: &mut State, args: &[String]) -> Result<()> {
+    let count = count(args)?;
+    state.debugger.as_mut().ok_or_else(no_debugger)?.back(count);
+    state.push_debugger_status();
+    Ok(())
+}
+
+fn run_debugger(state: &mut State, _args: &[String]) -> Result<()> {
+    start_debugger(state, Debugger::run)
+}
+
+// Moves the debugger to a worker thread for `forward`, it comes back to the session
+// once done
+fn start_debugger(
+    state: &mut State,
+    forward: impl FnOnce(&mut Debugger, &mut Vm) -> Result<usize> + Send + 'static,
+) -> Result<()> {
+    let mut debugger = state.debugger.take().ok_or_else(no_debugger)?;
+    let expr = debugger.current().clone();
+    let (sender, receiver) = mpsc::channel();
+    state.start_job_then(
+        expr,
+        Box::new(move |mut vm, _| {
+            let result = forward(&mut debugger, &mut vm);
+            let _ = sender.send(debugger);
+            result.map(drop)
+        }),
+        Box::new(move |state| {
+            state.debugger = receiver.try_recv().ok();
+            state.push_debugger_status();
+            Ok(())
+        }),
+    );
+    Ok(())
+}
+
+fn set_breakpoint(state: &mut State, args: &[String]) -> Result<()> {
+    let breakpoint = match (args.first().map(String::as_str), args.get(1)) {
+        (Some("unfold"), Some(name)) => Breakpoint::Unfold(name.clone()),
+        (Some("size"), Some(size)) => Breakpoint::Size(size.parse().map_err(|_| UsageError)?),
+        (None, _) => {
+            let lines = state
+                .debugger
+                .as_mut()
+                .ok_or_else(no_debugger)?
+                .breakpoints
+                .iter()
+                .enumerate()
+                .map(|(i, breakpoint)| format!("  {}: {}", i, breakpoint.describe()))
+                .collect::<Vec<_>>();
+            state.history.extend(lines);
+            return Ok(());
+        }
+        _ => return Err(UsageError.into()),
+    };
+    let line = format!("Breakpoint set: {}", breakpoint.describe());
+    state
+        .debugger
+        .as_mut()
+        .ok_or_else(no_debugger)?
+        .breakpoints
+        .push(breakpoint);
+    state.history.push(line);
+    Ok(())
+}
+
+fn unbreak(state: &mut State, args: &[String]) -> Result<()> {
+    let debugger = state.debugger.as_mut().ok_or_else(no_debugger)?;
+    match args.first() {
+        Some(index) => {
+            let index: usize = index.parse().map_err(|_| UsageError)?;
+            if index < debugger.breakpoints.len() {
+                debugger.breakpoints.remove(index);
+            }
+        }
+        None => debugger.breakpoints.clear(),
+    }
+    Ok(())
+}
+
+fn close(state: &mut State, _args: &[String]) -> Result<()> {
+    state.debugger.as_mut().ok_or_else(no_debugger)?;
+    state.debugger = None;
+    state.history.push("Debugger closed".to_string());
+    Ok(())
+}
+
+fn strategy(state: &mut State, args: &[String]) -> Result<()> {
+    match args.first() {
+        Some(name) => {
+            let strategy = name.parse::<Strategy>().map_err(|err| anyhow!(err))?;
+            state.vm.set_strategy(strategy);
+            state.history.push(format!("Strategy set to {}", strategy));
+        }
+        None => {
+            let available = Strategy::ALL.map(|strategy| strategy.name()).join(", ");
+            state.history.push(format!(
+                "Strategy: {} (available: {})",
+                state.vm.strategy(),
+                available
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn set(state: &mut State, args: &[String]) -> Result<()> {
+    match args {
+        [key] => {
+            let (key, _) = config::setting(key)?;
+            let value = state.config.get(key).unwrap_or_default();
+            state.history.push(format!("{} = {}", key, value));
+        }
+        [key, value] => {
+            state.config.set(key, value)?;
+            state.history.push(format!("{} = {}", key, value));
+        }
+        _ => return Err(UsageError.into()),
+    }
+    Ok(())
+}
+
+fn show_config(state: &mut State, _args: &[String]) -> Result<()> {
+    let width = SETTINGS
+        .iter()
+        .map(|(key, _)| key.len())
+        .max()
+        .unwrap_or_default();
+    for (key, _) in SETTINGS {
+        let value = state.config.get(key).unwrap_or_default();
+        state.history.push(format!(
+            "  {:width$}  {:5}  {}",
+            key,
+            value,
+            state.config.source(key),
+            width = width
+        ));
+    }
+    Ok(())
+}
+
+fn save_config(state: &mut State, args: &[String]) -> Result<()> {
+    let path = match args {
+        [] => None,
+        [path] => Some(std::path::Path::new(path)),
+        _ => return Err(UsageError.into()),
+    };
+    let path = state.config.save(path)?;
+    state
+        .history
+        .push(format!("Wrote settings to {}", path.display()));
+    Ok(())
+}
+
+fn defs(state: &mut State, _args: &[String]) -> Result<()> {
+    if state.vm.definitions().is_empty() {
+        state.history.push("No definitions".to_string());
+        return Ok(());
+    }
+    let lines = state
+        .vm
+        .definitions()
+        .iter()
+        .map(|(name, expr)| format!("{} := {}", name, state.fmt_expr(expr)))
+        .collect::<Vec<_>>();
+    state.history.extend(lines);
+    Ok(())
+}
+
+fn load(state: &mut State, args: &[String]) -> Result<()> {
+    let [path] = args else {
+        return Err(UsageError.into());
+    };
+    if state.replaying >= MAX_SCRIPT_DEPTH {
+        return Err(anyhow!("Scripts load each other too deeply"));
+    }
+    let contents = std::fs::read_to_string(path)?;
+    // The session keeps the `:load`, not the lines of the script
+    state.replaying += 1;
+    for line in contents.lines() {
+        state.exec(line.to_string());
+        if state.exit {
+            break;
+        }
+    }
+    state.replaying -= 1;
+    Ok(())
+}
+
+fn save(state: &mut State, args: &[String]) -> Result<()> {
+    let [path] = args else {
+        return Err(UsageError.into());
+    };
+    let mut script = SESSION_HEADER.to_string();
+    for line in &state.session {
+        script.push_str(line);
+        script.push('\n');
+    }
+    std::fs::write(path, script)?;
+    state
+        .history
+        .push(format!("Wrote {} lines to {}", state.session.len(), path));
+    Ok(())
+}
+
+fn restore(state: &mut State, args: &[String]) -> Result<()> {
+    let [path] = args else {
+        return Err(UsageError.into());
+    };
+    let contents = std::fs::read_to_string(path)?;
+    state.reset_session();
+    for line in contents.lines() {
+        state.exec(line.to_string());
+    }
+    state.history.push(format!(
+        "Restored {} lines from {}",
+        state.session.len(),
+        path
+    ));
+    Ok(())
+}
+
+fn clear(state: &mut State, _args: &[String]) -> Result<()> {
+    state.history.clear();
+    Ok(())
+}
+
+fn exit(state: &mut State, _args: &[String]) -> Result<()> {
+    state.exit = true;
+    state.history.push("Exiting...".to_string());
+    Ok(())
+}
+
+// Parses the expression made of `args` into the vm
+fn parse(state: &mut State, args: &[String]) -> Result<Expr> {
+    if args.is_empty() {
+        return Err(UsageError.into());
+    }
+    state.vm.parse_expr(&args.join(" "))?;
+    Ok(state.vm.get_expr().cloned().unwrap())
+}
+
+fn count(args: &[String]) -> Result<usize> {
+    match args.first() {
+        Some(count) => Ok(count.parse().map_err(|_| UsageError)?),
+        None => Ok(1),
+    }
+}
+
+fn no_debugger() -> anyhow::Error {
+    anyhow!("No term is being debugged, start with: :debug <expr>")
+}
@@ -0,0 +1,525 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::{
+    collections::HashMap,
+    env, fmt,
+    path::{Path, PathBuf},
+};
+
+const CONFIG_FOLDER: &str = "rambda";
+const CONFIG_FILE: &str = "config.yaml";
+// Project settings, in the working directory or next to a script
+const PROJECT_FILE: &str = ".rambda.yaml";
+const DEFAULT_CONFIG: &[u8] = include_bytes!("../default_config.yaml");
+
+// Prefix of the environment variables overriding settings, `RAMBDA_USE_COLOR=false`
+const ENV_PREFIX: &str = "RAMBDA_";
+
+/// Settings in the order of the config file, with the values each accepts.
+pub const SETTINGS: &[(&str, &str)] = &[
+    ("useUtf8", "true or false"),
+    ("useColor", "true or false"),
+    ("mergeArgs", "true or false"),
+    ("magic", "a single character or none"),
+    ("printEffect", "true or false"),
+    ("journal", "true or false"),
+];
+
+/// Where the effective value of a setting comes from, later layers win.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Default,
+    // The user config, or the file given with `--config`
+    User(PathBuf),
+    Project(PathBuf),
+    Env(String),
+    // Changed with `:set`
+    Session,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::User(path) => write!(f, "user config {}", path.display()),
+            Source::Project(path) => write!(f, "project config {}", path.display()),
+            Source::Env(var) => write!(f, "environment {}", var),
+            Source::Session => write!(f, "set this session"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    pub use_utf8: bool,
+    pub use_color: bool,
+    pub merge_args: bool,
+    pub magic: Option<char>,
+    pub print_effect: bool,
+    pub journal: bool,
+
+    // User config file, `:save-config` writes back to it
+    #[serde(skip)]
+    pub path: PathBuf,
+    #[serde(skip)]
+    sources: HashMap<&'static str, Source>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        serde_yaml::from_slice(DEFAULT_CONFIG).expect("default config is valid")
+    }
+}
+
+/// The `rambda` folder in the user config directory, created if missing.
+pub fn config_folder() -> Result<PathBuf> {
+    let config_folder = dirs::config_dir()
+        .ok_or(anyhow!("Failed to get config directory"))?
+        .join(CONFIG_FOLDER);
+    if !config_folder.exists() {
+        std::fs::create_dir_all(&config_folder)?;
+    }
+    Ok(config_folder)
+}
+
+impl Config {
+    /// Layers the settings: built-in defaults, the user config at `path` or in the user
+    /// config folder (created with the defaults on first use), a project `.rambda.yaml`
+    /// next to `script` or in the working directory, then environment variables.
+    pub fn load(path: Option<&Path>, script: Option<&Path>) -> Result<Self> {
+        let project_dir = match script {
+            Some(script) => script.parent().map(Path::to_path_buf),
+            None => env::current_dir().ok(),
+        };
+        Self::layered(path, project_dir, |var| env::var(var).ok())
+    }
+
+    // `load` with the environment read through `var`
+    fn layered(
+        path: Option<&Path>,
+        project_dir: Option<PathBuf>,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => {
+                let path = config_folder()?.join(CONFIG_FILE);
+                if !path.exists() {
+                    std::fs::write(&path, DEFAULT_CONFIG)?;
+                }
+                path
+            }
+        };
+
+        let mut config = Config {
+            path: path.clone(),
+            ..Config::default()
+        };
+        config.apply(read_file(&path)?, Source::User(path))?;
+
+        if let Some(project) = project_dir.map(|dir| dir.join(PROJECT_FILE))
+            && project.exists()
+        {
+            config.apply(read_file(&project)?, Source::Project(project))?;
+        }
+
+        config.apply_env(var)?;
+        Ok(config)
+    }
+
+    fn apply(&mut self, values: Mapping, source: Source) -> Result<()> {
+        for (key, value) in values {
+            let key = key.as_str().unwrap_or_default();
+            self.set_from(key, &yaml_to_string(&value), source.clone())
+                .map_err(|err| anyhow!("{}: {}", source, err))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the settings changed this session into the user config, or into a copy of
+    /// it at `path`. Only their lines change, so comments and the order of the file are
+    /// kept. Project and environment settings are left out.
+    pub fn save(&self, path: Option<&Path>) -> Result<PathBuf> {
+        let mut contents = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(anyhow!("Failed to read {}: {}", self.path.display(), err)),
+        };
+        let values = serde_yaml::to_value(self)?;
+        for (key, _) in SETTINGS {
+            if self.source(key) == Source::Session {
+                let value = serde_yaml::to_string(&values[key])?;
+                contents = set_line(&contents, key, value.trim());
+            }
+        }
+        let path = path.unwrap_or(&self.path);
+        std::fs::write(path, contents)?;
+        Ok(path.to_path_buf())
+    }
+
+    /// Where the effective value of a setting comes from.
+    pub fn source(&self, key: &str) -> Source {
+        self.sources.get(key).cloned().unwrap_or(Source::Default)
+    }
+
+    // `RAMBDA_<SETTING>` variables, then `NO_COLOR` (https://no-color.org)
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        for (key, _) in SETTINGS {
+            let name = env_var(key);
+            if let Some(value) = var(&name) {
+                self.set_from(key, &value, Source::Env(name.clone()))
+                    .map_err(|err| anyhow!("{}: {}", name, err))?;
+            }
+        }
+        if var("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            self.set_from("useColor", "false", Source::Env("NO_COLOR".to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Changes a setting by its name in the config file, for this session.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        self.set_from(key, value, Source::Session)
+    }
+
+    fn set_from(&mut self, key: &str, value: &str, source: Source) -> Result<()> {
+        let (key, allowed) = setting(key)?;
+        let invalid = || {
+            anyhow!(
+                "Invalid value '{}' for {}, expected {}",
+                value,
+                key,
+                allowed
+            )
+        };
+        let flag = || match value {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(invalid()),
+        };
+
+        match key {
+            "useUtf8" => self.use_utf8 = flag()?,
+            "useColor" => self.use_color = flag()?,
+            "mergeArgs" => self.merge_args = flag()?,
+            "printEffect" => self.print_effect = flag()?,
+            "journal" => self.journal = flag()?,
+            _ => {
+                let mut chars = value.chars();
+                self.magic = match (chars.next(), chars.next()) {
+                    _ if value == "none" => None,
+                    (Some(c), None) => Some(c),
+                    _ => return Err(invalid()),
+                }
+            }
+        }
+        self.sources.insert(key, source);
+        Ok(())
+    }
+
+    /// Current value of a setting, as `set` accepts it.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let value = match key {
+            "useUtf8" => self.use_utf8.to_string(),
+            "useColor" => self.use_color.to_string(),
+            "mergeArgs" => self.merge_args.to_string(),
+            "printEffect" => self.print_effect.to_string(),
+            "journal" => self.journal.to_string(),
+            "magic" => self.magic.map_or("none".to_string(), String::from),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+/// A setting by its name in the config file, with the values it accepts.
+pub fn setting(key: &str) -> Result<(&'static str, &'static str)> {
+    SETTINGS
+        .iter()
+        .find(|(name, _)| *name == key)
+        .copied()
+        .ok_or_else(|| {
+            anyhow!(
+                "Unknown setting '{}', expected one of: {}",
+                key,
+                SETTINGS
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+// `contents` with the line of `key` set to `value`, or the line added when there is none
+fn set_line(contents: &str, key: &str, value: &str) -> String {
+    let line = format!("{}: {}", key, value);
+    let mut lines = contents.lines().map(String::from).collect::<Vec<_>>();
+    match lines.iter_mut().find(|old| {
+        old.strip_prefix(key)
+            .is_some_and(|rest| rest.trim_start().starts_with(':'))
+    }) {
+        Some(old) => *old = line,
+        None => lines.push(line),
+    }
+    lines.join("\n") + "\n"
+}
+
+// An empty file has no settings
+fn read_file(path: &Path) -> Result<Mapping> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| anyhow!("Failed to read {}: {}", path.display(), err))?;
+    let values: Option<Mapping> = serde_yaml::from_str(&contents)
+        .map_err(|err| anyhow!("Failed to parse {}: {}", path.display(), err))?;
+    Ok(values.unwrap_or_default())
+}
+
+// `useUtf8` → `RAMBDA_USE_UTF8`
+fn env_var(key: &str) -> String {
+    let mut var = ENV_PREFIX.to_string();
+    for c in key.chars() {
+        if c.is_uppercase() {
+            var.push('_');
+        }
+        var.push(c.to_ascii_uppercase());
+    }
+    var
+}
+
+fn yaml_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => "none".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        _ => serde_yaml::to_string(value)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_values() {
+        let mut config = Config::default();
+        config.set("useColor", "false").unwrap();
+        assert!(!config.use_color);
+        assert_eq!(config.source("useColor"), Source::Session);
+        config.set("magic", "%").unwrap();
+        assert_eq!(config.magic, Some('%'));
+        config.set("magic", "none").unwrap();
+        assert_eq!(config.magic, None);
+
+        let err = config.set("useUtf8", "yes").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid value 'yes' for useUtf8, expected true or false"
+        );
+        assert!(config.set("magic", "ab").is_err());
+        assert!(config.set("magic", "").is_err());
+        assert!(config.use_utf8);
+        assert_eq!(config.source("useUtf8"), Source::Default);
+    }
+
+    #[test]
+    fn rejects_unknown_settings() {
+        let mut config = Config::default();
+        let err = config.set("colour", "true").unwrap_err();
+        assert!(err.to_string().starts_with("Unknown setting 'colour'"));
+        assert!(setting("colour").is_err());
+        assert_eq!(setting("journal").unwrap(), ("journal", "true or false"));
+        assert_eq!(config.get("colour"), None);
+    }
+
+    #[test]
+    fn every_setting_round_trips() {
+        let mut config = Config::default();
+        for (key, _) in SETTINGS {
+            let value = config.get(key).unwrap();
+            config.set(key, &value).unwrap();
+            assert_eq!(config.get(key), Some(value));
+        }
+        for (key, _) in SETTINGS {
+            assert_eq!(config.get(key), Config::default().get(key));
+        }
+    }
+
+    #[test]
+    fn save_changes_only_the_lines_set() {
+        let folder = env::temp_dir().join(format!("rambda-config-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let path = folder.join(CONFIG_FILE);
+        std::fs::write(&path, DEFAULT_CONFIG).unwrap();
+
+        let mut config = Config {
+            path: path.clone(),
+            ..Config::default()
+        };
+        config
+            .apply(read_file(&path).unwrap(), Source::User(path.clone()))
+            .unwrap();
+        config.set("useColor", "false").unwrap();
+        config.set("journal", "false").unwrap();
+        config.set("magic", "none").unwrap();
+        config.save(None).unwrap();
+
+        let default = String::from_utf8_lossy(DEFAULT_CONFIG)
+            .replace("useColor: true", "useColor: false")
+            .replace("journal: true", "journal: false")
+            .replace("magic: '\\'", "magic: null");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), default);
+        let saved = read_file(&path).unwrap();
+        let mut reloaded = Config::default();
+        reloaded.apply(saved, Source::User(path)).unwrap();
+        assert!(!reloaded.use_color && !reloaded.journal);
+        assert_eq!(reloaded.magic, None);
+
+        let copy = folder.join("copy.yaml");
+        config.set("mergeArgs", "false").unwrap();
+        config.save(Some(&copy)).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&copy).unwrap(),
+            default.replace("mergeArgs: true", "mergeArgs: false")
+        );
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn save_adds_missing_settings() {
+        assert_eq!(
+            set_line("# colors\nuseColor: true", "useUtf8", "false"),
+            "# colors\nuseColor: true\nuseUtf8: false\n"
+        );
+        assert_eq!(
+            set_line("useColorful: 1\nuseColor : true\n", "useColor", "false"),
+            "useColorful: 1\nuseColor: false\n"
+        );
+    }
+
+    // A user config and a project folder holding a project config, when given
+    struct Layers {
+        folder: PathBuf,
+        user: PathBuf,
+    }
+
+    impl Layers {
+        fn new(name: &str, user: &str, project: Option<&str>) -> Self {
+            let folder = env::temp_dir().join(format!("rambda-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(&folder).unwrap();
+            let user_path = folder.join(CONFIG_FILE);
+            std::fs::write(&user_path, user).unwrap();
+            if let Some(project) = project {
+                std::fs::write(folder.join(PROJECT_FILE), project).unwrap();
+            }
+            Layers {
+                folder,
+                user: user_path,
+            }
+        }
+
+        fn load(&self, vars: &[(&str, &str)]) -> Result<Config> {
+            let var = |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            };
+            Config::layered(Some(&self.user), Some(self.folder.clone()), var)
+        }
+    }
+
+    impl Drop for Layers {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.folder);
+        }
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let layers = Layers::new(
+            "layers",
+            "useUtf8: false\nuseColor: false\nmergeArgs: false\n",
+            Some("useColor: true\nmergeArgs: true\n"),
+        );
+        let config = layers.load(&[("RAMBDA_MERGE_ARGS", "false")]).unwrap();
+        assert!(!config.use_utf8 && config.use_color && !config.merge_args);
+        assert!(config.journal);
+
+        let project = layers.folder.join(PROJECT_FILE);
+        assert_eq!(config.source("useUtf8"), Source::User(layers.user.clone()));
+        assert_eq!(config.source("useColor"), Source::Project(project));
+        assert_eq!(
+            config.source("mergeArgs"),
+            Source::Env("RAMBDA_MERGE_ARGS".to_string())
+        );
+        assert_eq!(config.source("journal"), Source::Default);
+        assert_eq!(config.path, layers.user);
+    }
+
+    #[test]
+    fn no_color_overrides_the_other_layers() {
+        let layers = Layers::new("no-color", "useColor: true\n", None);
+        let config = layers
+            .load(&[("RAMBDA_USE_COLOR", "true"), ("NO_COLOR", "1")])
+            .unwrap();
+        assert!(!config.use_color);
+        assert_eq!(
+            config.source("useColor"),
+            Source::Env("NO_COLOR".to_string())
+        );
+
+        assert!(layers.load(&[("NO_COLOR", "")]).unwrap().use_color);
+    }
+
+    #[test]
+    fn empty_files_keep_the_defaults() {
+        let layers = Layers::new("empty", "", Some(""));
+        let config = layers.load(&[]).unwrap();
+        for (key, _) in SETTINGS {
+            assert_eq!(config.get(key), Config::default().get(key));
+            assert_eq!(config.source(key), Source::Default);
+        }
+    }
+
+    #[test]
+    fn invalid_layers_name_their_source() {
+        let layers = Layers::new("invalid-user", "magic: ab\n", None);
+        let err = layers.load(&[]).unwrap_err().to_string();
+        assert!(err.starts_with("user config "), "{}", err);
+        assert!(err.ends_with("Invalid value 'ab' for magic, expected a single character or none"));
+
+        let layers = Layers::new("invalid-project", "", Some("colour: true\n"));
+        let err = layers.load(&[]).unwrap_err().to_string();
+        assert!(err.starts_with("project config "), "{}", err);
+
+        let layers = Layers::new("invalid-env", "", None);
+        let err = layers
+            .load(&[("RAMBDA_JOURNAL", "1")])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("RAMBDA_JOURNAL: Invalid value '1'"),
+            "{}",
+            err
+        );
+
+        let layers = Layers::new("unparsable", "useColor: [", None);
+        assert!(layers
+            .load(&[])
+            .unwrap_err()
+            .to_string()
+            .starts_with("Failed to parse"));
+    }
+
+    #[test]
+    fn env_vars_are_named_after_the_settings() {
+        assert_eq!(env_var("useUtf8"), "RAMBDA_USE_UTF8");
+        assert_eq!(env_var("printEffect"), "RAMBDA_PRINT_EFFECT");
+        assert_eq!(env_var("magic"), "RAMBDA_MAGIC");
+    }
+}
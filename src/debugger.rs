@@ -0,0 +1,151 @@
+use anyhow::Result;
+use rambda::{
+    ast::{Expr, Path, RedType},
+    vm::{Step, Vm},
+};
+
+// Guards `run` against diverging terms when the vm has no step limit of its own
+const DEFAULT_RUN_LIMIT: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breakpoint {
+    // Stop after the named definition was unfolded
+    Unfold(String),
+
+    // Stop once the term grows past this many nodes
+    Size(usize),
+}
+
+impl Breakpoint {
+    fn is_hit(&self, before: &Expr, step: &Step) -> bool {
+        match self {
+            Breakpoint::Unfold(name) => {
+                matches!(&step.red_type, RedType::DeltaReduction(unfolded) if unfolded == name)
+            }
+            Breakpoint::Size(limit) => before.size() <= *limit && step.expr.size() > *limit,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Breakpoint::Unfold(name) => format!("unfold {}", name),
+            Breakpoint::Size(limit) => format!("size > {}", limit),
+        }
+    }
+}
+
+/// Steps through the reduction of one term, keeping every visited term so it can go back.
+pub struct Debugger {
+    terms: Vec<Expr>,
+    steps: Vec<Step>,
+    cursor: usize,
+    pub breakpoints: Vec<Breakpoint>,
+    pub message: Option<String>,
+}
+
+impl Debugger {
+    pub fn new(expr: Expr, vm: &mut Vm) -> Result<Self> {
+        let mut debugger = Debugger {
+            terms: vec![expr],
+            steps: Vec::new(),
+            cursor: 0,
+            breakpoints: Vec::new(),
+            message: None,
+        };
+        debugger.look_ahead(vm)?;
+        Ok(debugger)
+    }
+
+    pub fn current(&self) -> &Expr {
+        &self.terms[self.cursor]
+    }
+
+    pub fn position(&self) -> usize {
+        self.cursor
+    }
+
+    // Steps taken so far, up to the current term
+    pub fn trace(&self) -> &[Step] {
+        &self.steps[..self.cursor]
+    }
+
+    /// Where the next step contracts, `None` in normal form.
+    pub fn next_redex(&self) -> Option<&Path> {
+        self.steps
+            .get(self.cursor)
+            .and_then(|step| step.redex.as_ref())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cursor == self.steps.len()
+    }
+
+    // Computes the step after the current term, so its redex can be shown before taking it
+    fn look_ahead(&mut self, vm: &mut Vm) -> Result<()> {
+        if self.cursor < self.steps.len() {
+            return Ok(());
+        }
+        vm.set_expr(self.current().clone());
+        if let Some(step) = vm.step()? {
+            self.terms.push(step.expr.clone());
+            self.steps.push(step);
+        }
+        Ok(())
+    }
+
+    /// Takes up to `count` steps, stopping early at a breakpoint, a normal form, a term
+    /// deeper than the vm's depth limit or when the vm's cancel flag is set.
+    pub fn forward(&mut self, vm: &mut Vm, count: usize) -> Result<usize> {
+        self.message = None;
+        let mut taken = 0;
+        while taken < count && !self.is_finished() {
+            if vm.is_cancelled() {
+                self.message = Some(format!("Cancelled after {} steps", taken));
+                return Ok(taken);
+            }
+            if let Some(max) = vm.max_depth()
+                && self.current().depth() > max
+            {
+                self.message = Some(format!(
+                    "Stopped after {} steps, the term is deeper than {}",
+                    taken, max
+                ));
+                return Ok(taken);
+            }
+            self.cursor += 1;
+            taken += 1;
+            self.look_ahead(vm)?;
+
+            let (before, step) = (&self.terms[self.cursor - 1], &self.steps[self.cursor - 1]);
+            if let Some(breakpoint) = self
+                .breakpoints
+                .iter()
+                .find(|breakpoint| breakpoint.is_hit(before, step))
+            {
+                self.message = Some(format!("Breakpoint: {}", breakpoint.describe()));
+                return Ok(taken);
+            }
+        }
+        if self.is_finished() {
+            self.message = Some("Normal form reached".to_string());
+        }
+        Ok(taken)
+    }
+
+    pub fn back(&mut self, count: usize) -> usize {
+        self.message = None;
+        let taken = count.min(self.cursor);
+        self.cursor -= taken;
+        taken
+    }
+
+    /// Runs until a normal form or a breakpoint, bounded by the vm's step limit.
+    pub fn run(&mut self, vm: &mut Vm) -> Result<usize> {
+        let limit = vm.max_steps().unwrap_or(DEFAULT_RUN_LIMIT);
+        let taken = self.forward(vm, limit)?;
+        if self.message.is_none() {
+            self.message = Some(format!("Stopped after {} steps", taken));
+        }
+        Ok(taken)
+    }
+}
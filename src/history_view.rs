@@ -0,0 +1,291 @@
+use crate::ansi;
+use tui::{
+    style::{Color, Style},
+    text::{Span, Spans},
+};
+
+/// Scroll position and search state of the TUI history pane. Until the user scrolls up
+/// the pane follows the newest output.
+#[derive(Default)]
+pub struct HistoryView {
+    // First visible line, only used while detached from the bottom
+    top: usize,
+    // Lines that fit in the pane at the last draw
+    height: usize,
+    detached: bool,
+    /// Query of the open search prompt
+    pub search: Option<String>,
+    /// Line of the last match jumped to
+    pub matched: Option<usize>,
+}
+
+impl HistoryView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn max_top(&self, total: usize) -> usize {
+        total.saturating_sub(self.height)
+    }
+
+    fn top(&self, total: usize) -> usize {
+        if self.detached {
+            self.top.min(self.max_top(total))
+        } else {
+            self.max_top(total)
+        }
+    }
+
+    pub fn is_following(&self) -> bool {
+        !self.detached
+    }
+
+    /// Lines moved by PageUp / PageDown, keeping one line of context.
+    pub fn page(&self) -> usize {
+        self.height.saturating_sub(1).max(1)
+    }
+
+    pub fn scroll_up(&mut self, lines: usize, total: usize) {
+        self.top = self.top(total).saturating_sub(lines);
+        self.detached = true;
+    }
+
+    pub fn scroll_down(&mut self, lines: usize, total: usize) {
+        let top = self.top(total) + lines;
+        if top >= self.max_top(total) {
+            self.follow();
+        } else {
+            self.top = top;
+        }
+    }
+
+    pub fn scroll_to_top(&mut self) {
+        self.top = 0;
+        self.detached = true;
+    }
+
+    pub fn follow(&mut self) {
+        self.detached = false;
+    }
+
+    /// Keeps the view on the same lines after `count` lines were dropped from the front.
+    pub fn trimmed(&mut self, count: usize) {
+        self.top = self.top.saturating_sub(count);
+        self.matched = self.matched.and_then(|matched| matched.checked_sub(count));
+    }
+
+    /// Jumps to the next line matching the search query, towards older lines unless
+    /// `forward`. Returns false when there is none.
+    pub fn find(&mut self, lines: &[String], forward: bool) -> bool {
+        let Some(query) = self.query() else {
+            return false;
+        };
+        let start = self.matched.unwrap_or(if forward {
+            self.top(lines.len())
+        } else {
+            self.top(lines.len()) + self.height
+        });
+        let is_match = |i: &usize| contains(&lines[*i], &query);
+        let found = if forward {
+            (start + 1..lines.len()).find(is_match)
+        } else {
+            (0..start.min(lines.len())).rev().find(is_match)
+        };
+        let Some(line) = found else {
+            return false;
+        };
+
+        self.matched = Some(line);
+        self.top = line.saturating_sub(self.height / 2);
+        self.detached = true;
+        if self.top >= self.max_top(lines.len()) {
+            self.follow();
+        }
+        true
+    }
+
+    /// Opens the search prompt with an empty query.
+    pub fn open_search(&mut self) {
+        self.search = Some(String::new());
+        self.matched = None;
+    }
+
+    /// Edits the query of the open search prompt. The next search starts over from the
+    /// visible lines, since the last match may not match the new query.
+    pub fn edit_search(&mut self, edit: impl FnOnce(&mut String)) {
+        if let Some(query) = &mut self.search {
+            edit(query);
+            self.matched = None;
+        }
+    }
+
+    pub fn close_search(&mut self) {
+        self.search = None;
+        self.matched = None;
+    }
+
+    fn query(&self) -> Option<String> {
+        self.search
+            .as_deref()
+            .filter(|query| !query.is_empty())
+            .map(str::to_ascii_lowercase)
+    }
+
+    /// The lines visible in a pane `height` lines tall, with search matches highlighted.
+    pub fn visible(&mut self, lines: &[String], height: usize) -> Vec<Spans<'static>> {
+        self.height = height;
+        let top = self.top(lines.len());
+        let query = self.query();
+
+        lines
+            .iter()
+            .enumerate()
+            .skip(top)
+            .take(height)
+            .map(|(i, line)| match &query {
+                Some(query) if contains(line, query) => {
+                    highlight_matches(&ansi::strip(line), query, self.matched == Some(i))
+                }
+                _ => ansi::to_spans(line),
+            })
+            .collect()
+    }
+}
+
+// Case-insensitive search on the printed text. ASCII lowercasing keeps byte offsets intact.
+fn contains(line: &str, query: &str) -> bool {
+    ansi::strip(line).to_ascii_lowercase().contains(query)
+}
+
+// Matching lines lose their colors so the matches stand out
+fn highlight_matches(text: &str, query: &str, current: bool) -> Spans<'static> {
+    let style = Style::default().fg(Color::Black).bg(if current {
+        Color::Indexed(208)
+    } else {
+        Color::Yellow
+    });
+    let lowercase = text.to_ascii_lowercase();
+    let mut spans = Vec::new();
+    let mut end = 0;
+    for (start, _) in lowercase.match_indices(query) {
+        spans.push(Span::raw(text[end..start].to_string()));
+        end = start + query.len();
+        spans.push(Span::styled(text[start..end].to_string(), style));
+    }
+    spans.push(Span::raw(text[end..].to_string()));
+
+    Spans::from(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|text| text.to_string()).collect()
+    }
+
+    fn searching(query: &str, height: usize) -> HistoryView {
+        let mut view = HistoryView::new();
+        view.height = height;
+        view.open_search();
+        view.edit_search(|search| search.push_str(query));
+        view
+    }
+
+    #[test]
+    fn find_cycles_towards_older_lines() {
+        let lines = lines(&["foo 1", "bar", "foo 2", "bar", "foo 3", "bar"]);
+        let mut view = searching("foo", 2);
+        assert!(view.find(&lines, false));
+        assert_eq!(view.matched, Some(4));
+        assert!(view.find(&lines, false));
+        assert_eq!(view.matched, Some(2));
+        assert!(view.find(&lines, false));
+        assert_eq!(view.matched, Some(0));
+        assert!(!view.find(&lines, false));
+        assert_eq!(view.matched, Some(0));
+
+        assert!(view.find(&lines, true));
+        assert_eq!(view.matched, Some(2));
+    }
+
+    #[test]
+    fn find_scrolls_to_the_match() {
+        let lines: Vec<String> = (0..20).map(|i| format!("line {i}")).collect();
+        let mut view = searching("line 3", 4);
+        assert!(view.find(&lines, false));
+        assert_eq!(view.matched, Some(3));
+        assert!(!view.is_following());
+        assert_eq!(view.top(lines.len()), 1);
+    }
+
+    #[test]
+    fn find_ignores_case_and_colors() {
+        let lines = lines(&["\x1b[1;32mFoo\x1b[0m", "bar"]);
+        let mut view = searching("fOO", 2);
+        assert!(view.find(&lines, false));
+        assert_eq!(view.matched, Some(0));
+    }
+
+    #[test]
+    fn find_without_query_or_match() {
+        let lines = lines(&["foo", "bar"]);
+        let mut view = searching("", 2);
+        assert!(!view.find(&lines, false));
+        view.edit_search(|query| query.push_str("baz"));
+        assert!(!view.find(&lines, false));
+        assert_eq!(view.matched, None);
+    }
+
+    #[test]
+    fn editing_the_query_resets_the_match() {
+        let lines = lines(&["foo", "bar", "foo"]);
+        let mut view = searching("foo", 3);
+        assert!(view.find(&lines, false));
+        assert!(view.find(&lines, false));
+        assert_eq!(view.matched, Some(0));
+
+        view.edit_search(|query| *query = "bar".to_string());
+        assert_eq!(view.matched, None);
+        assert!(view.find(&lines, false));
+        assert_eq!(view.matched, Some(1));
+    }
+
+    #[test]
+    fn trimming_keeps_the_match_on_its_line() {
+        let lines = lines(&["foo", "bar", "foo"]);
+        let mut view = searching("foo", 1);
+        assert!(view.find(&lines, false));
+        assert_eq!(view.matched, Some(2));
+        view.trimmed(1);
+        assert_eq!(view.matched, Some(1));
+        view.trimmed(2);
+        assert_eq!(view.matched, None);
+    }
+
+    #[test]
+    fn close_search_forgets_the_match() {
+        let lines = lines(&["foo"]);
+        let mut view = searching("foo", 1);
+        assert!(view.find(&lines, false));
+        view.close_search();
+        assert_eq!(view.search, None);
+        assert_eq!(view.matched, None);
+        assert!(!view.find(&lines, false));
+    }
+
+    #[test]
+    fn visible_highlights_matches() {
+        let lines = lines(&["a foo b foo", "bar"]);
+        let mut view = searching("foo", 2);
+        let spans = view.visible(&lines, 2);
+        let texts: Vec<&str> = spans[0]
+            .0
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect();
+        assert_eq!(texts, ["a ", "foo", " b ", "foo", ""]);
+        assert_eq!(spans[1].0.len(), 1);
+    }
+}
@@ -0,0 +1,335 @@
+use crate::commands;
+
+// Same palette as `Expr::fmt_with_config`
+const LAMBDA: &str = "\x1b[1m\x1b[38;5;2m";
+const BINDER: &str = "\x1b[1m\x1b[38;5;4m";
+const PUNCT: &str = "\x1b[38;5;240m";
+const COMMAND: &str = "\x1b[1m\x1b[38;5;5m";
+const DEFINED: &str = "\x1b[38;5;3m";
+const UNBOUND: &str = "\x1b[4m\x1b[38;5;1m";
+const UNBALANCED: &str = "\x1b[1m\x1b[48;5;52m";
+const MATCHING: &str = "\x1b[48;5;238m";
+
+// Commands whose arguments end with an expression
+const EXPR_COMMANDS: &[&str] = &[
+    "bytecode", "cek", "debug", "diagram", "eval", "export", "krivine", "need", "stats", "steps",
+    "tree", "type",
+];
+
+/// An input line colored for the terminal, with the problems found in it.
+pub struct Highlighted {
+    pub ansi: String,
+    pub problems: Vec<String>,
+}
+
+/// Names and numbers, as read by the lexer. λ is a letter but never part of a name.
+pub fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() && c != 'λ' || c == '_'
+}
+
+/// Colors an input line as it is being typed: commands, definitions and the expressions
+/// they take, with the parenthesis matching the one at byte offset `cursor` marked.
+/// Unbalanced parentheses and names that are neither bound nor `is_defined` are flagged.
+pub fn highlight(line: &str, cursor: usize, is_defined: impl Fn(&str) -> bool) -> Highlighted {
+    let chars = line.chars().collect::<Vec<_>>();
+    let cursor = line[..cursor.min(line.len())].chars().count();
+    let mut styles = vec![""; chars.len()];
+    let mut problems = Vec::new();
+
+    let mut words = words(&chars).into_iter().peekable();
+    let expr_start = match words.next() {
+        Some((start, end))
+            if words
+                .peek()
+                .is_some_and(|&(s, e)| word(&chars, s, e) == ":=") =>
+        {
+            styles[start..end].fill(BINDER);
+            let (start, end) = words.next().unwrap();
+            styles[start..end].fill(PUNCT);
+            Some(end)
+        }
+        Some((start, end)) if command_name(&word(&chars, start, end), &is_defined).is_some() => {
+            styles[start..end].fill(COMMAND);
+            let command = command_name(&word(&chars, start, end), &is_defined).unwrap();
+            let mut expr_start = end;
+            if command == "export"
+                && let Some((_, end)) = words.next()
+            {
+                expr_start = end;
+            }
+            while let Some((start, end)) =
+                words.next_if(|&(s, e)| word(&chars, s, e).starts_with("--"))
+            {
+                styles[start..end].fill(PUNCT);
+                expr_start = end;
+                if word(&chars, start, end) == "--svg"
+                    && let Some((_, end)) = words.next()
+                {
+                    expr_start = end;
+                }
+            }
+            EXPR_COMMANDS.contains(&command).then_some(expr_start)
+        }
+        Some((start, end)) if word(&chars, start, end).starts_with(':') => {
+            styles[start..end].fill(UNBOUND);
+            problems.push(format!("unknown command {}", word(&chars, start, end)));
+            None
+        }
+        // Anything else is evaluated
+        Some(_) => Some(0),
+        _ => None,
+    };
+
+    let mut pairs = Vec::new();
+    if let Some(start) = expr_start {
+        let mut bound: Vec<String> = Vec::new();
+        // Open parens with the number of binders in scope before them
+        let mut open: Vec<(usize, usize)> = Vec::new();
+        let mut in_binders = false;
+        let mut i = start;
+        while i < chars.len() {
+            match chars[i] {
+                'λ' | '\\' => {
+                    styles[i] = LAMBDA;
+                    in_binders = true;
+                }
+                '.' => {
+                    styles[i] = PUNCT;
+                    in_binders = false;
+                }
+                ',' => styles[i] = PUNCT,
+                '(' => {
+                    styles[i] = PUNCT;
+                    open.push((i, bound.len()));
+                }
+                ')' => match open.pop() {
+                    Some((start, in_scope)) => {
+                        styles[i] = PUNCT;
+                        bound.truncate(in_scope);
+                        pairs.push((start, i));
+                    }
+                    None => {
+                        styles[i] = UNBALANCED;
+                        problems.push("unmatched ')'".to_string());
+                    }
+                },
+                c if is_name_char(c) => {
+                    let end = (i..chars.len())
+                        .find(|&j| !is_name_char(chars[j]))
+                        .unwrap_or(chars.len());
+                    let name = word(&chars, i, end);
+                    let style = if in_binders {
+                        BINDER
+                    } else if bound.contains(&name) {
+                        ""
+                    } else if is_defined(&name) {
+                        DEFINED
+                    } else {
+                        let problem = format!("unbound name {}", name);
+                        if !problems.contains(&problem) {
+                            problems.push(problem);
+                        }
+                        UNBOUND
+                    };
+                    styles[i..end].fill(style);
+                    if in_binders {
+                        bound.push(name);
+                    }
+                    i = end;
+                    continue;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        for (start, _) in open {
+            styles[start] = UNBALANCED;
+            problems.push("unclosed '('".to_string());
+        }
+    }
+
+    // The paren under the cursor, or the one just before it
+    let mut matching = vec![false; chars.len()];
+    let near_cursor = |i: usize| i == cursor || i + 1 == cursor;
+    if let Some(&(open, close)) = pairs
+        .iter()
+        .find(|&&(open, close)| near_cursor(open) || near_cursor(close))
+    {
+        matching[open] = true;
+        matching[close] = true;
+    }
+
+    let mut ansi = String::new();
+    let mut current = ("", false);
+    for (i, c) in chars.iter().enumerate() {
+        if (styles[i], matching[i]) != current {
+            current = (styles[i], matching[i]);
+            ansi.push_str("\x1b[0m");
+            ansi.push_str(styles[i]);
+            if matching[i] {
+                ansi.push_str(MATCHING);
+            }
+        }
+        ansi.push(*c);
+    }
+    if current != ("", false) {
+        ansi.push_str("\x1b[0m");
+    }
+
+    Highlighted { ansi, problems }
+}
+
+// Name of the command a first word runs, as `State::exec` finds it
+fn command_name(word: &str, is_defined: &impl Fn(&str) -> bool) -> Option<&'static str> {
+    match word.strip_prefix(':') {
+        Some(name) => commands::find(name),
+        None => commands::find_bare(word, is_defined),
+    }
+    .map(|command| command.name)
+}
+
+// Char ranges of the whitespace separated words
+fn words(chars: &[char]) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in chars.iter().enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(i),
+            (true, Some(s)) => {
+                words.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, chars.len()));
+    }
+    words
+}
+
+fn word(chars: &[char], start: usize, end: usize) -> String {
+    chars[start..end].iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defined(name: &str) -> bool {
+        ["id", "eval"].contains(&name)
+    }
+
+    // The runs of `line` and the styles they are drawn with, unstyled runs left out
+    fn spans(line: &str, cursor: usize) -> Vec<(String, String)> {
+        let ansi = highlight(line, cursor, defined).ansi;
+        ansi.split("\x1b[0m")
+            .filter_map(|run| {
+                let mut style = String::new();
+                let mut text = run;
+                while let Some(rest) = text.strip_prefix("\x1b[") {
+                    let end = rest.find('m').unwrap() + 1;
+                    style.push_str(&text[..end + 2]);
+                    text = &rest[end..];
+                }
+                (!style.is_empty()).then(|| (style, text.to_string()))
+            })
+            .collect()
+    }
+
+    fn span(style: &str, text: &str) -> (String, String) {
+        (style.to_string(), text.to_string())
+    }
+
+    fn problems(line: &str) -> Vec<String> {
+        highlight(line, 0, defined).problems
+    }
+
+    #[test]
+    fn colors_an_expression() {
+        assert_eq!(
+            spans("\\x. x id y", 0),
+            [
+                span(LAMBDA, "\\"),
+                span(BINDER, "x"),
+                span(PUNCT, "."),
+                span(DEFINED, "id"),
+                span(UNBOUND, "y"),
+            ]
+        );
+        assert_eq!(problems("\\x. x id y"), ["unbound name y"]);
+    }
+
+    #[test]
+    fn colors_a_definition() {
+        assert_eq!(
+            spans("two := λf x.f (f x)", 0),
+            [
+                span(BINDER, "two"),
+                span(PUNCT, ":="),
+                span(LAMBDA, "λ"),
+                span(BINDER, "f"),
+                span(BINDER, "x"),
+                span(PUNCT, "."),
+                span(PUNCT, "("),
+                span(PUNCT, ")"),
+            ]
+        );
+    }
+
+    #[test]
+    fn colors_commands_and_flags() {
+        assert_eq!(
+            spans(":diagram --svg out.svg id", 0),
+            [
+                span(COMMAND, ":diagram"),
+                span(PUNCT, "--svg"),
+                span(DEFINED, "id"),
+            ]
+        );
+        assert_eq!(spans(":help eval", 0), [span(COMMAND, ":help")]);
+        assert_eq!(spans("clear", 0), [span(COMMAND, "clear")]);
+        assert_eq!(spans("quit", 0), [span(COMMAND, "quit")]);
+    }
+
+    #[test]
+    fn defined_names_are_not_bare_commands() {
+        assert_eq!(spans("eval", 0), [span(DEFINED, "eval")]);
+    }
+
+    #[test]
+    fn flags_unknown_commands() {
+        assert_eq!(spans(":nope x", 0), [span(UNBOUND, ":nope")]);
+        assert_eq!(problems(":nope x"), ["unknown command :nope"]);
+    }
+
+    #[test]
+    fn binders_go_out_of_scope_with_their_parens() {
+        assert_eq!(problems("(\\x.x) x"), ["unbound name x"]);
+        assert_eq!(problems("\\x.(x) x"), Vec::<String>::new());
+        assert_eq!(problems("y y"), ["unbound name y"]);
+    }
+
+    #[test]
+    fn flags_unbalanced_parens() {
+        assert_eq!(problems("id)"), ["unmatched ')'"]);
+        assert_eq!(problems("(id"), ["unclosed '('"]);
+        assert_eq!(spans("(id", 0)[0], span(UNBALANCED, "("));
+    }
+
+    #[test]
+    fn marks_the_paren_matching_the_cursor() {
+        let matching = format!("{PUNCT}{MATCHING}");
+        let marked = [
+            span(&matching, "("),
+            span(DEFINED, "id"),
+            span(&matching, ")"),
+            span(PUNCT, "("),
+            span(DEFINED, "id"),
+            span(PUNCT, ")"),
+        ];
+        assert_eq!(spans("(id) (id)", 4), marked);
+        assert_eq!(spans("(id) (id)", 0), marked);
+        assert_eq!(spans("(id) (id)", 2)[0], span(PUNCT, "("));
+    }
+}
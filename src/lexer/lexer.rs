@@ -5,6 +5,12 @@ pub struct Lexer {
     position: usize,
 }
 
+impl Default for Lexer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Lexer {
     pub fn new() -> Self {
         Lexer {
@@ -65,7 +65,7 @@ impl Parser {
                 Some(lambda_expr)
             }
             Some(Token::Identifier(name)) => {
-                let result = var(&name);
+                let result = var(name);
                 self.advance();
                 Some(result)
             }
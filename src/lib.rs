@@ -1,9 +1,35 @@
+//! A lambda calculus interpreter.
+//!
+//! The [`vm::Vm`] parses terms, keeps definitions and reduces terms with a chosen
+//! strategy and limits:
+//!
+//! ```
+//! use rambda::vm::{Options, Termination, Vm};
+//!
+//! let mut vm = Vm::with_options(Options {
+//!     max_steps: Some(1000),
+//!     prelude: "id := \\x.x\nk := \\x.\\y.x".to_string(),
+//!     ..Options::default()
+//! })?;
+//! let evaluation = vm.evaluate("k id id")?;
+//! assert_eq!(evaluation.termination, Termination::NormalForm);
+//! assert_eq!(evaluation.result.fmt_with_config(false, true, true), "λx.x");
+//!
+//! // Steps are computed as they are asked for, even for a term without a normal form
+//! let omega = vm.parse("(\\x.x x) (\\x.x x)")?;
+//! vm.set_expr(omega);
+//! assert_eq!(vm.steps().take(3).count(), 3);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
 pub mod ast;
 pub mod lexer;
+pub mod render;
 pub mod vm;
 
 pub mod prelude {
     pub use crate::ast::*;
     pub use crate::lexer::*;
+    pub use crate::render::*;
     pub use crate::vm::*;
 }
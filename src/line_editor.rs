@@ -0,0 +1,383 @@
+use crate::input_highlight::is_name_char;
+use anyhow::Result;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+// Older entries are dropped from the history file when it is loaded
+const MAX_HISTORY_ENTRIES: usize = 1_000;
+
+/// Single-line input with a cursor, word-wise editing and a history of submitted lines
+/// that is persisted to `history_file`.
+#[derive(Default)]
+pub struct LineEditor {
+    buffer: String,
+    // Byte offset into `buffer`, always on a char boundary
+    cursor: usize,
+    history: Vec<String>,
+    // Index into `history` while recalling, `draft` keeps the line being typed
+    recall: Option<usize>,
+    draft: String,
+    history_file: Option<PathBuf>,
+}
+
+impl LineEditor {
+    /// Loads the history from `path` and appends every submitted line to it.
+    pub fn with_history_file(path: PathBuf) -> Result<Self> {
+        let history = match fs::read_to_string(&path) {
+            Ok(contents) => contents.lines().map(String::from).collect::<Vec<_>>(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let skip = history.len().saturating_sub(MAX_HISTORY_ENTRIES);
+        if skip > 0 {
+            fs::write(&path, history[skip..].join("\n") + "\n")?;
+        }
+
+        Ok(Self {
+            history: history[skip..].to_vec(),
+            history_file: Some(path),
+            ..Self::default()
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.buffer
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Byte offset of the cursor.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Column of the cursor, in characters from the start of the line.
+    pub fn cursor_column(&self) -> usize {
+        self.buffer[..self.cursor].chars().count()
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.buffer.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    /// Inserts pasted text, joining its lines with spaces.
+    pub fn paste(&mut self, text: &str) {
+        let text = text.lines().collect::<Vec<_>>().join(" ");
+        self.buffer.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+    }
+
+    pub fn backspace(&mut self) {
+        if let Some(start) = self.prev_boundary() {
+            self.buffer.drain(start..self.cursor);
+            self.cursor = start;
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if let Some(end) = self.next_boundary() {
+            self.buffer.drain(self.cursor..end);
+        }
+    }
+
+    pub fn left(&mut self) {
+        if let Some(start) = self.prev_boundary() {
+            self.cursor = start;
+        }
+    }
+
+    pub fn right(&mut self) {
+        if let Some(end) = self.next_boundary() {
+            self.cursor = end;
+        }
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.buffer.len();
+    }
+
+    pub fn word_left(&mut self) {
+        self.cursor = self.word_start();
+    }
+
+    pub fn word_right(&mut self) {
+        self.cursor = self.word_end();
+    }
+
+    pub fn delete_word_back(&mut self) {
+        let start = self.word_start();
+        self.buffer.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    pub fn delete_word_forward(&mut self) {
+        let end = self.word_end();
+        self.buffer.drain(self.cursor..end);
+    }
+
+    pub fn delete_to_start(&mut self) {
+        self.buffer.drain(..self.cursor);
+        self.cursor = 0;
+    }
+
+    pub fn delete_to_end(&mut self) {
+        self.buffer.truncate(self.cursor);
+    }
+
+    /// Replaces the line with the previous history entry.
+    pub fn history_prev(&mut self) {
+        let index = match self.recall {
+            Some(0) => return,
+            Some(index) => index - 1,
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = self.buffer.clone();
+                self.history.len() - 1
+            }
+        };
+        self.recall = Some(index);
+        self.set_line(self.history[index].clone());
+    }
+
+    /// Replaces the line with the next history entry, or the draft past the newest one.
+    pub fn history_next(&mut self) {
+        let Some(index) = self.recall else {
+            return;
+        };
+        if index + 1 < self.history.len() {
+            self.recall = Some(index + 1);
+            self.set_line(self.history[index + 1].clone());
+        } else {
+            self.recall = None;
+            let draft = std::mem::take(&mut self.draft);
+            self.set_line(draft);
+        }
+    }
+
+    /// Takes the line, recording it in the history. The line is returned even when it
+    /// could not be appended to the history file, along with the error; the file is
+    /// left alone from then on.
+    pub fn submit(&mut self) -> (String, Result<()>) {
+        let line = std::mem::take(&mut self.buffer);
+        self.cursor = 0;
+        self.recall = None;
+        self.draft.clear();
+
+        let mut persisted = Ok(());
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if let Some(path) = &self.history_file {
+                persisted = append_line(path, &line);
+                if persisted.is_err() {
+                    self.history_file = None;
+                }
+            }
+        }
+        (line, persisted)
+    }
+
+    fn set_line(&mut self, line: String) {
+        self.buffer = line;
+        self.cursor = self.buffer.len();
+    }
+
+    fn prev_boundary(&self) -> Option<usize> {
+        self.buffer[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map(|(i, _)| i)
+    }
+
+    fn next_boundary(&self) -> Option<usize> {
+        self.buffer[self.cursor..]
+            .chars()
+            .next()
+            .map(|c| self.cursor + c.len_utf8())
+    }
+
+    // Start of the word before the cursor, skipping the separators in between
+    fn word_start(&self) -> usize {
+        let before = &self.buffer[..self.cursor];
+        let trimmed = before.trim_end_matches(|c: char| !is_name_char(c));
+        trimmed
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !is_name_char(*c))
+            .map_or(0, |(i, c)| i + c.len_utf8())
+    }
+
+    // End of the word after the cursor, skipping the separators in between
+    fn word_end(&self) -> usize {
+        let after = &self.buffer[self.cursor..];
+        let skipped = after.len() - after.trim_start_matches(|c: char| !is_name_char(c)).len();
+        after[skipped..]
+            .char_indices()
+            .find(|(_, c)| !is_name_char(*c))
+            .map_or(self.buffer.len(), |(i, _)| self.cursor + skipped + i)
+    }
+}
+
+fn append_line(path: &Path, line: &str) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(line: &str) -> LineEditor {
+        let mut editor = LineEditor::default();
+        editor.paste(line);
+        editor
+    }
+
+    #[test]
+    fn inserts_at_the_cursor() {
+        let mut editor = typed("λx.");
+        editor.home();
+        editor.right();
+        editor.insert('y');
+        assert_eq!(editor.as_str(), "λyx.");
+        assert_eq!(editor.cursor(), "λy".len());
+        assert_eq!(editor.cursor_column(), 2);
+    }
+
+    #[test]
+    fn moves_and_deletes_over_multibyte_chars() {
+        let mut editor = typed("aλb");
+        editor.left();
+        editor.backspace();
+        assert_eq!(editor.as_str(), "ab");
+        assert_eq!(editor.cursor(), 1);
+        editor.delete();
+        assert_eq!(editor.as_str(), "a");
+        editor.delete();
+        editor.right();
+        assert_eq!(editor.cursor(), 1);
+        editor.home();
+        editor.backspace();
+        editor.left();
+        assert_eq!((editor.as_str(), editor.cursor()), ("a", 0));
+    }
+
+    #[test]
+    fn pastes_lines_joined_by_spaces() {
+        let mut editor = typed("id := \\x.x");
+        editor.home();
+        editor.paste("a\nb\n");
+        assert_eq!(editor.as_str(), "a bid := \\x.x");
+        assert_eq!(editor.cursor(), 3);
+    }
+
+    #[test]
+    fn moves_by_words() {
+        let mut editor = typed("foo := (\\x_1. bar)");
+        editor.word_left();
+        assert_eq!(&editor.as_str()[editor.cursor()..], "bar)");
+        editor.word_left();
+        assert_eq!(&editor.as_str()[editor.cursor()..], "x_1. bar)");
+        editor.word_left();
+        editor.word_left();
+        assert_eq!(editor.cursor(), 0);
+
+        editor.word_right();
+        assert_eq!(&editor.as_str()[editor.cursor()..], " := (\\x_1. bar)");
+        editor.word_right();
+        assert_eq!(&editor.as_str()[editor.cursor()..], ". bar)");
+        editor.end();
+        editor.word_right();
+        assert_eq!(editor.cursor(), editor.as_str().len());
+    }
+
+    #[test]
+    fn deletes_by_words() {
+        let mut editor = typed("foo bar  baz");
+        editor.delete_word_back();
+        assert_eq!(editor.as_str(), "foo bar  ");
+        editor.delete_word_back();
+        assert_eq!(editor.as_str(), "foo ");
+
+        let mut editor = typed("foo bar baz");
+        editor.home();
+        editor.delete_word_forward();
+        assert_eq!(editor.as_str(), " bar baz");
+        editor.delete_word_forward();
+        assert_eq!(editor.as_str(), " baz");
+        assert_eq!(editor.cursor(), 0);
+    }
+
+    #[test]
+    fn deletes_to_either_end() {
+        let mut editor = typed("foo bar");
+        editor.word_left();
+        editor.delete_to_end();
+        assert_eq!(editor.as_str(), "foo ");
+        editor.left();
+        editor.delete_to_start();
+        assert_eq!((editor.as_str(), editor.cursor()), (" ", 0));
+    }
+
+    #[test]
+    fn recalls_history_and_the_draft() {
+        let mut editor = LineEditor::default();
+        for line in ["one", "two", "two", "  "] {
+            editor.paste(line);
+            assert_eq!(editor.submit().0, line);
+        }
+        assert!(editor.is_empty());
+
+        editor.paste("dra");
+        editor.history_prev();
+        assert_eq!(editor.as_str(), "two");
+        assert_eq!(editor.cursor(), 3);
+        editor.history_prev();
+        assert_eq!(editor.as_str(), "one");
+        editor.history_prev();
+        assert_eq!(editor.as_str(), "one");
+        editor.history_next();
+        assert_eq!(editor.as_str(), "two");
+        editor.history_next();
+        assert_eq!(editor.as_str(), "dra");
+        editor.history_next();
+        assert_eq!(editor.as_str(), "dra");
+    }
+
+    #[test]
+    fn persists_submitted_lines() {
+        let path = std::env::temp_dir().join(format!("rambda-history-{}", std::process::id()));
+        fs::write(&path, "old\n").unwrap();
+
+        let mut editor = LineEditor::with_history_file(path.clone()).unwrap();
+        editor.paste("new");
+        assert!(editor.submit().1.is_ok());
+        editor.history_prev();
+        editor.history_prev();
+        assert_eq!(editor.as_str(), "old");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old\nnew\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_line_when_the_history_file_fails() {
+        let path = std::env::temp_dir().join("rambda-missing-folder/history");
+        let mut editor = LineEditor::with_history_file(path).unwrap();
+        editor.paste("a");
+        let (line, persisted) = editor.submit();
+        assert_eq!(line, "a");
+        assert!(persisted.is_err());
+        editor.paste("b");
+        assert!(editor.submit().1.is_ok());
+    }
+}
@@ -1,17 +1,33 @@
+mod ansi;
+mod commands;
+mod config;
+mod debugger;
+mod history_view;
+mod input_highlight;
+mod line_editor;
+mod run_eval;
 mod run_file;
 mod run_repl;
 mod run_tui;
 mod state;
+mod worker;
 
 pub use state::State;
 
 use clap::{Args, Parser, Subcommand};
+use rambda::{ast::Strategy, vm::Backend};
+use run_eval::OutputFormat;
+use std::{error::Error, path::PathBuf, process::ExitCode, thread};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct CliArgs {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Read the config from this file instead of the user config folder
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -19,6 +35,8 @@ enum Commands {
     Tui,
     Repl,
     File(FileArgs),
+    /// Evaluate a single expression and exit (3: parse error, 4: no normal form within the step limit)
+    Eval(EvalArgs),
 }
 
 #[derive(Args, Debug, Clone)]
@@ -27,21 +45,74 @@ struct FileArgs {
     pub path: String,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+#[derive(Args, Debug, Clone)]
+struct EvalArgs {
+    /// Expression to evaluate, read from stdin when omitted or `-`
+    #[arg()]
+    pub expr: Option<String>,
+
+    /// Reduction strategy: normal or applicative
+    #[arg(short, long, default_value_t = Strategy::NormalOrder)]
+    pub strategy: Strategy,
+
+    /// Evaluation backend: substitution, krivine for the weak head normal form of a closed
+    /// term, cek to evaluate a closed term by value, or bytecode to compile it for krivine
+    #[arg(short, long, default_value_t = Backend::Substitution)]
+    pub backend: Backend,
+
+    /// Give up after this many reduction steps
+    #[arg(short, long, default_value_t = 10_000)]
+    pub max_steps: usize,
+
+    /// Only print the result
+    #[arg(short, long, conflicts_with = "trace")]
+    pub quiet: bool,
+
+    /// Print every reduction step
+    #[arg(short, long)]
+    pub trace: bool,
+
+    /// Print reduction statistics, to stderr for LaTeX and Markdown
+    #[arg(long)]
+    pub stats: bool,
+
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Disable colored output, also implied when stdout is not a terminal
+    #[arg(long)]
+    pub no_color: bool,
+}
+
+fn main() -> Result<ExitCode, Box<dyn Error + Send + Sync>> {
     let args = CliArgs::parse();
+    // Printing a term recurses as deep as it is, like reducing it on a worker does
+    let session = thread::Builder::new()
+        .stack_size(worker::STACK_SIZE)
+        .spawn(move || run(args))?;
+    session
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+fn run(args: CliArgs) -> Result<ExitCode, Box<dyn Error + Send + Sync>> {
+    let config = args.config.as_deref();
 
     match args.command {
         Commands::Tui => {
-            run_tui::run_tui()?;
+            run_tui::run_tui(config)?;
         }
         Commands::File(args) => {
-            run_file::run_file(args.path)?;
+            run_file::run_file(args.path, config)?;
         }
         Commands::Repl => {
-            run_repl::run_repl()?;
+            run_repl::run_repl(config)?;
+        }
+        Commands::Eval(args) => {
+            return Ok(run_eval::run_eval(args, config)?);
         }
     }
 
     println!("Bye, and thanks for using Rambda! <3");
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
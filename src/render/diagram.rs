@@ -0,0 +1,233 @@
+use crate::ast::Expr;
+
+// Pieces of a lambda diagram in grid units, rows grow downwards
+enum Segment {
+    // Abstraction bar spanning the columns of its body
+    Bar { row: usize, from: usize, to: usize },
+
+    // Application link joining the function and argument wires
+    Link { row: usize, from: usize, to: usize },
+
+    // Variable wire hanging from its binder's bar
+    Wire { col: usize, from: usize, to: usize },
+}
+
+struct Layout {
+    segments: Vec<Segment>,
+    width: usize,
+    height: usize,
+}
+
+// Placed subdiagram, `out` is the column of its output wire which currently ends at `end`
+struct Placed {
+    width: usize,
+    height: usize,
+    out: usize,
+    end: usize,
+}
+
+fn layout(expr: &Expr) -> Layout {
+    // Free variables hang from an extra row above the diagram
+    let top = if expr.free_vars().is_empty() { 0 } else { 1 };
+    let mut segments = Vec::new();
+    let placed = place(expr, 0, top, &mut Vec::new(), &mut segments);
+
+    let height = top + placed.height;
+    segments.push(Segment::Wire {
+        col: placed.out,
+        from: placed.end,
+        to: height,
+    });
+
+    Layout {
+        segments,
+        width: placed.width,
+        height,
+    }
+}
+
+fn place(
+    expr: &Expr,
+    x: usize,
+    y: usize,
+    scope: &mut Vec<(String, usize)>,
+    segments: &mut Vec<Segment>,
+) -> Placed {
+    match expr {
+        Expr::Var(name) => {
+            let binder = scope
+                .iter()
+                .rev()
+                .find(|(param, _)| param == name)
+                .map_or(0, |(_, row)| *row);
+            segments.push(Segment::Wire {
+                col: x,
+                from: binder,
+                to: y,
+            });
+            Placed {
+                width: 1,
+                height: 0,
+                out: x,
+                end: y,
+            }
+        }
+        Expr::Abs(param, body) => {
+            scope.push((param.clone(), y));
+            let body = place(body, x, y + 1, scope, segments);
+            scope.pop();
+            segments.push(Segment::Bar {
+                row: y,
+                from: x,
+                to: x + body.width - 1,
+            });
+            Placed {
+                height: body.height + 1,
+                ..body
+            }
+        }
+        Expr::Apl(e1, e2) => {
+            let func = place(e1, x, y, scope, segments);
+            let arg = place(e2, x + func.width, y, scope, segments);
+            let link = y + func.height.max(arg.height);
+            for placed in [&func, &arg] {
+                if placed.end < link {
+                    segments.push(Segment::Wire {
+                        col: placed.out,
+                        from: placed.end,
+                        to: link,
+                    });
+                }
+            }
+            segments.push(Segment::Link {
+                row: link,
+                from: func.out,
+                to: arg.out,
+            });
+            Placed {
+                width: func.width + arg.width,
+                height: func.height.max(arg.height) + 1,
+                out: func.out,
+                end: link,
+            }
+        }
+    }
+}
+
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+/// Renders John Tromp's lambda diagram of `expr` with box-drawing characters
+/// (plain ASCII unless `utf8`).
+pub fn expr_to_diagram(expr: &Expr, utf8: bool) -> String {
+    let layout = layout(expr);
+    let mut grid = vec![vec![0u8; layout.width * 2 - 1]; layout.height + 1];
+
+    for segment in &layout.segments {
+        match *segment {
+            // Bars reach past their outer wires, links stop at them
+            Segment::Bar { row, from, to } => {
+                for cell in &mut grid[row][from * 2..=to * 2] {
+                    *cell |= LEFT | RIGHT;
+                }
+            }
+            Segment::Link { row, from, to } => {
+                grid[row][from * 2] |= RIGHT;
+                for cell in &mut grid[row][from * 2 + 1..to * 2] {
+                    *cell |= LEFT | RIGHT;
+                }
+                grid[row][to * 2] |= LEFT;
+            }
+            Segment::Wire { col, from, to } => {
+                for (row, cells) in grid.iter_mut().enumerate().take(to + 1).skip(from) {
+                    let mut flags = UP | DOWN;
+                    if row == from {
+                        flags &= !UP;
+                    }
+                    if row == to {
+                        flags &= !DOWN;
+                    }
+                    cells[col * 2] |= flags;
+                }
+            }
+        }
+    }
+
+    grid.iter()
+        .map(|row| {
+            let line = row
+                .iter()
+                .map(|flags| cell_char(*flags, utf8))
+                .collect::<String>();
+            line.trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn cell_char(flags: u8, utf8: bool) -> char {
+    if !utf8 {
+        return match flags {
+            0 => ' ',
+            f if f & (LEFT | RIGHT) == 0 => '|',
+            f if f & (UP | DOWN) == 0 => '-',
+            _ => '+',
+        };
+    }
+    match flags {
+        0 => ' ',
+        UP => '╵',
+        DOWN => '╷',
+        LEFT => '╴',
+        RIGHT => '╶',
+        f if f == UP | DOWN => '│',
+        f if f == LEFT | RIGHT => '─',
+        f if f == DOWN | RIGHT => '┌',
+        f if f == DOWN | LEFT => '┐',
+        f if f == UP | RIGHT => '└',
+        f if f == UP | LEFT => '┘',
+        f if f == UP | DOWN | RIGHT => '├',
+        f if f == UP | DOWN | LEFT => '┤',
+        f if f == DOWN | LEFT | RIGHT => '┬',
+        f if f == UP | LEFT | RIGHT => '┴',
+        _ => '┼',
+    }
+}
+
+const UNIT: usize = 16;
+const MARGIN: usize = 8;
+
+/// Renders John Tromp's lambda diagram of `expr` as a standalone SVG document.
+pub fn expr_to_diagram_svg(expr: &Expr) -> String {
+    let layout = layout(expr);
+    let x = |col: usize| MARGIN + col * UNIT + UNIT / 2;
+    let y = |row: usize| MARGIN + row * UNIT;
+
+    let mut lines = vec![format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+        w = layout.width * UNIT + 2 * MARGIN,
+        h = layout.height * UNIT + 2 * MARGIN,
+    )];
+    lines.push(format!(
+        "  <g stroke=\"black\" stroke-width=\"{}\" stroke-linecap=\"square\">",
+        UNIT / 4
+    ));
+    for segment in &layout.segments {
+        let (x1, y1, x2, y2) = match *segment {
+            Segment::Bar { row, from, to } => {
+                (x(from) - UNIT / 3, y(row), x(to) + UNIT / 3, y(row))
+            }
+            Segment::Link { row, from, to } => (x(from), y(row), x(to), y(row)),
+            Segment::Wire { col, from, to } => (x(col), y(from), x(col), y(to)),
+        };
+        lines.push(format!(
+            "    <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>",
+            x1, y1, x2, y2
+        ));
+    }
+    lines.push("  </g>".to_string());
+    lines.push("</svg>".to_string());
+    lines.join("\n")
+}
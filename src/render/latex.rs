@@ -0,0 +1,67 @@
+use super::notation::Notation;
+use crate::{
+    ast::{Branch, Expr},
+    vm::Step,
+};
+
+const LATEX: Notation = Notation {
+    lambda: "\\lambda ",
+    param_sep: "\\, ",
+    dot: ".\\, ",
+    app_sep: "\\; ",
+    open: "(",
+    close: ")",
+    highlight: ("\\underline{", "}"),
+    var: latex_var,
+};
+
+pub fn expr_to_latex(expr: &Expr) -> String {
+    LATEX.fmt(expr, None)
+}
+
+/// Like `expr_to_latex`, underlining the subterm at `redex`.
+pub fn expr_to_latex_highlighted(expr: &Expr, redex: &[Branch]) -> String {
+    LATEX.fmt(expr, Some(redex))
+}
+
+/// Renders a reduction as an `align*` block, one step per line with the redex
+/// contracted by the next step underlined.
+pub fn trace_to_latex(input: &Expr, steps: &[Step]) -> String {
+    let fmt = |expr: &Expr, next: Option<&Step>| match next.and_then(|step| step.redex.as_ref()) {
+        Some(path) => expr_to_latex_highlighted(expr, path),
+        None => expr_to_latex(expr),
+    };
+
+    let mut lines = vec![format!("  & {}", fmt(input, steps.first()))];
+    for (i, step) in steps.iter().enumerate() {
+        lines.push(format!(
+            "  {}\\; & {}",
+            step.kind.fmt_latex(),
+            fmt(&step.expr, steps.get(i + 1))
+        ));
+    }
+    format!(
+        "\\begin{{align*}}\n{}\n\\end{{align*}}",
+        lines.join(" \\\\\n")
+    )
+}
+
+// Numbered names become subscripts (`y_1` → `y_{1}`), longer names are set upright
+fn latex_var(name: &str) -> String {
+    let (base, index) = match name.rsplit_once('_') {
+        Some((base, index)) if !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()) => {
+            (base, Some(index))
+        }
+        _ => (name, None),
+    };
+    let base = base.replace('_', "\\_");
+    let base = if base.chars().count() > 1 {
+        format!("\\mathrm{{{}}}", base)
+    } else {
+        base
+    };
+    match index {
+        Some(index) => format!("{}_{{{}}}", base, index),
+        None => base,
+    }
+}
@@ -0,0 +1,53 @@
+use super::notation::Notation;
+use crate::{
+    ast::{Branch, Expr},
+    vm::Step,
+};
+
+const MARKDOWN: Notation = Notation {
+    lambda: "λ",
+    param_sep: ",",
+    dot: ".",
+    app_sep: " ",
+    open: "(",
+    close: ")",
+    highlight: ("**", "**"),
+    var: markdown_var,
+};
+
+pub fn expr_to_markdown(expr: &Expr) -> String {
+    MARKDOWN.fmt(expr, None)
+}
+
+/// Like `expr_to_markdown`, setting the subterm at `redex` in bold.
+pub fn expr_to_markdown_highlighted(expr: &Expr, redex: &[Branch]) -> String {
+    MARKDOWN.fmt(expr, Some(redex))
+}
+
+/// Renders a reduction as a Markdown table using plain Unicode symbols, with the
+/// redex contracted by the next step in bold.
+pub fn trace_to_markdown(input: &Expr, steps: &[Step]) -> String {
+    let fmt = |expr: &Expr, next: Option<&Step>| match next.and_then(|step| step.redex.as_ref()) {
+        Some(path) => expr_to_markdown_highlighted(expr, path),
+        None => expr_to_markdown(expr),
+    };
+
+    let mut lines = vec![
+        "| Step | Rule | Term |".to_string(),
+        "| ---: | :--- | :--- |".to_string(),
+        format!("| 0 | | {} |", fmt(input, steps.first())),
+    ];
+    for (i, step) in steps.iter().enumerate() {
+        lines.push(format!(
+            "| {} | {} | {} |",
+            i + 1,
+            step.kind.fmt_unicode(),
+            fmt(&step.expr, steps.get(i + 1))
+        ));
+    }
+    lines.join("\n")
+}
+
+fn markdown_var(name: &str) -> String {
+    name.replace('_', "\\_")
+}
@@ -0,0 +1,10 @@
+mod diagram;
+mod latex;
+mod markdown;
+mod notation;
+mod tree;
+
+pub use diagram::{expr_to_diagram, expr_to_diagram_svg};
+pub use latex::{expr_to_latex, expr_to_latex_highlighted, trace_to_latex};
+pub use markdown::{expr_to_markdown, expr_to_markdown_highlighted, trace_to_markdown};
+pub use tree::{expr_to_ascii_tree, expr_to_dot};
@@ -0,0 +1,114 @@
+use crate::ast::{Branch, Expr};
+
+// Symbols used to print a term in one of the export formats
+pub(crate) struct Notation {
+    pub lambda: &'static str,
+    pub param_sep: &'static str,
+    pub dot: &'static str,
+    pub app_sep: &'static str,
+    pub open: &'static str,
+    pub close: &'static str,
+    pub highlight: (&'static str, &'static str),
+    pub var: fn(&str) -> String,
+}
+
+impl Notation {
+    // Prints `expr`, wrapping the subterm at `highlight` in the highlight markers
+    pub fn fmt(&self, expr: &Expr, highlight: Option<&[Branch]>) -> String {
+        if highlight.is_some_and(|path| path.is_empty()) {
+            return self.highlighted(self.fmt(expr, None));
+        }
+
+        match expr {
+            Expr::Var(name) => (self.var)(name),
+            Expr::Abs(param, body) => {
+                let mut params = vec![(self.var)(param)];
+                let mut body = body;
+                let mut highlight = descend(highlight, Branch::Body);
+                // Curried abstractions are merged unless the highlight starts in between
+                while let Expr::Abs(param, inner) = &**body
+                    && !highlight.is_some_and(|path| path.is_empty())
+                {
+                    params.push((self.var)(param));
+                    body = inner;
+                    highlight = descend(highlight, Branch::Body);
+                }
+                format!(
+                    "{}{}{}{}",
+                    self.lambda,
+                    params.join(self.param_sep),
+                    self.dot,
+                    self.fmt(body, highlight)
+                )
+            }
+            Expr::Apl(_, _) => self.fmt_spine(expr, highlight),
+        }
+    }
+
+    fn fmt_spine(&self, expr: &Expr, highlight: Option<&[Branch]>) -> String {
+        let mut head = expr;
+        let mut args = Vec::new();
+        while let Expr::Apl(e1, e2) = head {
+            args.push(&**e2);
+            head = e1;
+        }
+        args.reverse();
+
+        // Leading `Func` branches walk down the spine, from the last argument to the head
+        let depth = highlight.map_or(0, |path| {
+            path.iter()
+                .take_while(|branch| **branch == Branch::Func)
+                .count()
+        });
+        let rest = highlight.map(|path| &path[depth..]);
+
+        let mut parts = Vec::with_capacity(args.len() + 1);
+        let head_highlight = if depth == args.len() { rest } else { None };
+        parts.push(self.part(head, head_highlight, matches!(head, Expr::Abs(_, _))));
+        for (i, arg) in args.iter().enumerate() {
+            let arg_highlight = if depth < args.len() && i == args.len() - 1 - depth {
+                descend(rest, Branch::Arg)
+            } else {
+                None
+            };
+            parts.push(self.part(arg, arg_highlight, !matches!(arg, Expr::Var(_))));
+        }
+
+        // A path ending inside the spine highlights an application prefix
+        if rest.is_some_and(|path| path.is_empty()) && depth > 0 && depth < args.len() {
+            let split = args.len() - depth + 1;
+            let prefix = self.highlighted(parts[..split].join(self.app_sep));
+            return std::iter::once(prefix)
+                .chain(parts[split..].iter().cloned())
+                .collect::<Vec<_>>()
+                .join(self.app_sep);
+        }
+        parts.join(self.app_sep)
+    }
+
+    fn part(&self, expr: &Expr, highlight: Option<&[Branch]>, parens: bool) -> String {
+        if !parens {
+            return self.fmt(expr, highlight);
+        }
+        if highlight.is_some_and(|path| path.is_empty()) {
+            return self.highlighted(format!(
+                "{}{}{}",
+                self.open,
+                self.fmt(expr, None),
+                self.close
+            ));
+        }
+        format!("{}{}{}", self.open, self.fmt(expr, highlight), self.close)
+    }
+
+    fn highlighted(&self, s: String) -> String {
+        format!("{}{}{}", self.highlight.0, s, self.highlight.1)
+    }
+}
+
+fn descend(path: Option<&[Branch]>, branch: Branch) -> Option<&[Branch]> {
+    match path?.split_first() {
+        Some((first, rest)) if *first == branch => Some(rest),
+        _ => None,
+    }
+}
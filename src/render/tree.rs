@@ -0,0 +1,156 @@
+use crate::ast::{Branch, Expr, Strategy};
+
+/// Renders the syntax tree as a Graphviz digraph, filling the node of the next
+/// redex. With `binder_edges` every bound occurrence gets a dashed edge to its binder.
+pub fn expr_to_dot(expr: &Expr, strategy: Strategy, binder_edges: bool) -> String {
+    let mut dot = DotBuilder {
+        lines: Vec::new(),
+        redex: expr.redex_path(strategy),
+        binder_edges,
+        next_id: 0,
+    };
+    dot.node(expr, &mut Vec::new(), &mut Vec::new());
+
+    let mut lines = vec![
+        "digraph expr {".to_string(),
+        "  node [shape=circle, fontname=\"monospace\"];".to_string(),
+    ];
+    lines.extend(dot.lines);
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+struct DotBuilder {
+    lines: Vec<String>,
+    redex: Option<Vec<Branch>>,
+    binder_edges: bool,
+    next_id: usize,
+}
+
+impl DotBuilder {
+    // Emits `expr` and its children, returns the id of its node
+    fn node(
+        &mut self,
+        expr: &Expr,
+        path: &mut Vec<Branch>,
+        scope: &mut Vec<(String, usize)>,
+    ) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let label = match expr {
+            Expr::Var(name) => name.clone(),
+            Expr::Abs(param, _) => format!("λ{}", param),
+            Expr::Apl(_, _) => "@".to_string(),
+        };
+        let style = if self.redex.as_deref() == Some(path.as_slice()) {
+            ", style=filled, fillcolor=\"#ffd54f\""
+        } else {
+            ""
+        };
+        self.lines
+            .push(format!("  n{} [label=\"{}\"{}];", id, label, style));
+
+        match expr {
+            Expr::Var(name) => {
+                let binder = scope.iter().rev().find(|(param, _)| param == name);
+                if let Some((_, binder)) = binder
+                    && self.binder_edges
+                {
+                    self.lines.push(format!(
+                        "  n{} -> n{} [style=dashed, color=gray, constraint=false];",
+                        id, binder
+                    ));
+                }
+            }
+            Expr::Abs(param, body) => {
+                scope.push((param.clone(), id));
+                path.push(Branch::Body);
+                let child = self.node(body, path, scope);
+                path.pop();
+                scope.pop();
+                self.lines.push(format!("  n{} -> n{};", id, child));
+            }
+            Expr::Apl(e1, e2) => {
+                for (branch, child) in [(Branch::Func, e1), (Branch::Arg, e2)] {
+                    path.push(branch);
+                    let child = self.node(child, path, scope);
+                    path.pop();
+                    self.lines.push(format!("  n{} -> n{};", id, child));
+                }
+            }
+        }
+
+        id
+    }
+}
+
+/// Renders the syntax tree as indented box-drawing art (plain ASCII unless `utf8`),
+/// marking the next redex.
+pub fn expr_to_ascii_tree(expr: &Expr, strategy: Strategy, utf8: bool) -> String {
+    let redex = expr.redex_path(strategy);
+    let mut lines = Vec::new();
+    ascii_node(
+        expr,
+        &mut Vec::new(),
+        redex.as_deref(),
+        utf8,
+        "",
+        "",
+        &mut lines,
+    );
+    lines.join("\n")
+}
+
+fn ascii_node(
+    expr: &Expr,
+    path: &mut Vec<Branch>,
+    redex: Option<&[Branch]>,
+    utf8: bool,
+    prefix: &str,
+    child_prefix: &str,
+    lines: &mut Vec<String>,
+) {
+    let (tee, corner, pipe, blank) = if utf8 {
+        ("├── ", "└── ", "│   ", "    ")
+    } else {
+        ("+-- ", "`-- ", "|   ", "    ")
+    };
+
+    let label = match expr {
+        Expr::Var(name) => name.clone(),
+        Expr::Abs(param, _) => format!("{}{}", if utf8 { "λ" } else { "\\" }, param),
+        Expr::Apl(_, _) => "@".to_string(),
+    };
+    let marker = if redex == Some(path.as_slice()) {
+        if utf8 {
+            "  ← redex"
+        } else {
+            "  <- redex"
+        }
+    } else {
+        ""
+    };
+    lines.push(format!("{}{}{}", prefix, label, marker));
+
+    let children: Vec<(Branch, &Expr)> = match expr {
+        Expr::Var(_) => vec![],
+        Expr::Abs(_, body) => vec![(Branch::Body, body)],
+        Expr::Apl(e1, e2) => vec![(Branch::Func, e1), (Branch::Arg, e2)],
+    };
+    let count = children.len();
+    for (i, (branch, child)) in children.into_iter().enumerate() {
+        let last = i + 1 == count;
+        path.push(branch);
+        ascii_node(
+            child,
+            path,
+            redex,
+            utf8,
+            &format!("{}{}", child_prefix, if last { corner } else { tee }),
+            &format!("{}{}", child_prefix, if last { blank } else { pipe }),
+            lines,
+        );
+        path.pop();
+    }
+}
@@ -0,0 +1,314 @@
+use super::{EvalArgs, State};
+use anyhow::Result;
+use clap::ValueEnum;
+use rambda::{
+    ast::{Expr, Strategy},
+    render,
+    vm::{Backend, Evaluation, MachineRun, Stats, Step, Termination},
+};
+use serde_json::{json, Value};
+use std::{
+    io::{self, IsTerminal, Read},
+    path::Path,
+    process::ExitCode,
+};
+
+pub const EXIT_PARSE_ERROR: u8 = 3;
+pub const EXIT_NO_NORMAL_FORM: u8 = 4;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Latex,
+    Markdown,
+}
+
+pub fn run_eval(args: EvalArgs, config: Option<&Path>) -> Result<ExitCode> {
+    let input = match args.expr.as_deref() {
+        Some(expr) if expr != "-" => expr.to_string(),
+        _ => read_stdin()?,
+    };
+
+    let mut state = State::new(config, None)?;
+    if args.no_color || args.format != OutputFormat::Text || !io::stdout().is_terminal() {
+        state.config.use_color = false;
+    }
+    state.vm.set_strategy(args.strategy);
+    state.vm.set_max_steps(Some(args.max_steps));
+
+    let input_expr = match state.vm.parse(&input) {
+        Ok(expr) => expr,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return Ok(ExitCode::from(EXIT_PARSE_ERROR));
+        }
+    };
+    if args.backend != Backend::Substitution {
+        return run_machine(&state, &args, &input_expr);
+    }
+    let evaluation = state.vm.evaluate_expr(input_expr);
+    let (input_expr, steps, result) = (&evaluation.input, &evaluation.steps, &evaluation.result);
+
+    let output = match args.format {
+        OutputFormat::Text if args.stats => format!(
+            "{}\n{}",
+            fmt_text(&state, &args, input_expr, steps, result),
+            evaluation.stats
+        ),
+        OutputFormat::Text => fmt_text(&state, &args, input_expr, steps, result),
+        OutputFormat::Json if args.stats => {
+            let mut value = fmt_json(&state, &args, &evaluation);
+            value["stats"] = stats_to_json(&evaluation.stats);
+            value.to_string()
+        }
+        OutputFormat::Json => fmt_json(&state, &args, &evaluation).to_string(),
+        OutputFormat::Latex => fmt_latex(&args, input_expr, steps, result),
+        OutputFormat::Markdown => fmt_markdown(&args, input_expr, steps, result),
+    };
+    println!("{}", output);
+    if args.stats && matches!(args.format, OutputFormat::Latex | OutputFormat::Markdown) {
+        eprintln!("{}", evaluation.stats);
+    }
+
+    if matches!(
+        evaluation.termination,
+        Termination::SizeLimit | Termination::DepthLimit
+    ) {
+        eprintln!("No normal form, the term grew too large");
+        return Ok(ExitCode::from(EXIT_NO_NORMAL_FORM));
+    }
+    if !evaluation.is_normal_form() {
+        eprintln!("No normal form within {} steps", args.max_steps);
+        return Ok(ExitCode::from(EXIT_NO_NORMAL_FORM));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_machine(state: &State, args: &EvalArgs, input: &Expr) -> Result<ExitCode> {
+    if matches!(args.format, OutputFormat::Latex | OutputFormat::Markdown) {
+        eprintln!("Error: the {} backend prints text or JSON", args.backend);
+        return Ok(ExitCode::FAILURE);
+    }
+    // The machines have their own evaluation order
+    if args.strategy != Strategy::NormalOrder {
+        eprintln!(
+            "Error: --strategy {} only applies to the substitution backend",
+            args.strategy.name()
+        );
+        return Ok(ExitCode::FAILURE);
+    }
+    if args.backend == Backend::Bytecode && args.trace {
+        eprintln!("Error: the bytecode backend has no trace");
+        return Ok(ExitCode::FAILURE);
+    }
+    let run = match args.backend {
+        Backend::Cek => state.vm.evaluate_cek(input, args.trace),
+        Backend::Bytecode => state.vm.evaluate_bytecode(input),
+        _ => state.vm.evaluate_krivine(input, args.trace),
+    };
+    let run = match run {
+        Ok(run) => run,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let output = match args.format {
+        OutputFormat::Json => {
+            let mut value = fmt_machine_json(state, args, input, &run);
+            if args.stats {
+                value["stats"] = json!({ "beta": run.beta, "delta": run.delta });
+            }
+            value.to_string()
+        }
+        _ if args.stats => format!(
+            "{}\n{} steps: {} beta, {} delta",
+            fmt_machine_text(state, args, input, &run),
+            run.steps(),
+            run.beta,
+            run.delta
+        ),
+        _ => fmt_machine_text(state, args, input, &run),
+    };
+    println!("{}", output);
+
+    match run.termination {
+        Termination::NormalForm => Ok(ExitCode::SUCCESS),
+        _ => {
+            eprintln!("No value within {} steps", args.max_steps);
+            Ok(ExitCode::from(EXIT_NO_NORMAL_FORM))
+        }
+    }
+}
+
+fn read_stdin() -> Result<String> {
+    let mut contents = String::new();
+    io::stdin().read_to_string(&mut contents)?;
+    let lines = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect::<Vec<_>>();
+    Ok(lines.join(" "))
+}
+
+fn fmt_expr(state: &State, expr: &Expr) -> String {
+    expr.fmt_with_config(
+        state.config.use_color,
+        state.config.use_utf8,
+        state.config.merge_args,
+    )
+}
+
+fn fmt_text(state: &State, args: &EvalArgs, input: &Expr, steps: &[Step], result: &Expr) -> String {
+    if args.quiet {
+        return fmt_expr(state, result);
+    }
+
+    let mut lines = vec![fmt_expr(state, input)];
+    if args.trace {
+        for step in steps {
+            lines.push(format!(
+                "  {} {}",
+                step.red_type
+                    .fmt_with_config(state.config.use_color, state.config.use_utf8),
+                fmt_expr(state, &step.expr),
+            ));
+        }
+    }
+    lines.push(format!(
+        "=> {} ({} steps)",
+        fmt_expr(state, result),
+        steps.len()
+    ));
+    lines.join("\n")
+}
+
+// `normalForm` is whether the evaluation ended in one, as the exit code says
+fn fmt_json(state: &State, args: &EvalArgs, evaluation: &Evaluation) -> Value {
+    let (input, steps, result) = (&evaluation.input, &evaluation.steps, &evaluation.result);
+    if args.quiet {
+        json!({
+            "result": fmt_expr(state, result),
+            "normalForm": evaluation.is_normal_form(),
+        })
+    } else {
+        let mut value = json!({
+            "input": fmt_expr(state, input),
+            "strategy": args.strategy.name(),
+            "result": fmt_expr(state, result),
+            "normalForm": evaluation.is_normal_form(),
+            "steps": steps.len(),
+        });
+        if args.trace {
+            value["trace"] = steps
+                .iter()
+                .map(|step| {
+                    json!({
+                        "rule": step.kind.name(),
+                        "expr": fmt_expr(state, &step.expr),
+                    })
+                })
+                .collect();
+        }
+        value
+    }
+}
+
+fn fmt_machine_text(state: &State, args: &EvalArgs, input: &Expr, run: &MachineRun) -> String {
+    let config = &state.config;
+    let result = run.result.as_ref().map(|result| fmt_expr(state, result));
+    if args.quiet {
+        return result.unwrap_or_default();
+    }
+
+    let mut lines = vec![fmt_expr(state, input)];
+    for machine_state in &run.trace {
+        let fmt_state =
+            machine_state.fmt_with_config(config.use_color, config.use_utf8, config.merge_args);
+        lines.push(match &machine_state.transition {
+            Some(transition) => format!(
+                "  {} {}",
+                transition.fmt_with_config(config.use_color),
+                fmt_state
+            ),
+            None => format!("  {}", fmt_state),
+        });
+    }
+    if let Some(result) = result {
+        lines.push(format!("=> {} ({} steps)", result, run.steps()));
+    }
+    lines.join("\n")
+}
+
+fn fmt_machine_json(state: &State, args: &EvalArgs, input: &Expr, run: &MachineRun) -> Value {
+    let result = run.result.as_ref().map(|result| fmt_expr(state, result));
+    let whnf = run.termination == Termination::NormalForm;
+    if args.quiet {
+        return json!({ "result": result, "weakHeadNormalForm": whnf });
+    }
+    let mut value = json!({
+        "input": fmt_expr(state, input),
+        "backend": args.backend.name(),
+        "result": result,
+        "weakHeadNormalForm": whnf,
+        "steps": run.steps(),
+    });
+    if args.trace {
+        value["trace"] = run
+            .trace
+            .iter()
+            .map(|machine_state| {
+                json!({
+                    "transition": machine_state.transition.as_ref().map(|t| t.name()),
+                    "state": machine_state.fmt_with_config(false, state.config.use_utf8, state.config.merge_args),
+                })
+            })
+            .collect();
+    }
+    value
+}
+
+fn stats_to_json(stats: &Stats) -> Value {
+    json!({
+        "beta": stats.beta,
+        "alpha": stats.alpha,
+        "delta": stats.delta,
+        "contextual": stats.contextual,
+        "substitutions": stats.substitutions,
+        "maxSize": stats.max_size,
+        "maxDepth": stats.max_depth,
+        "seconds": stats.time.as_secs_f64(),
+        "unfolded": stats.unfolded,
+    })
+}
+
+fn fmt_latex(args: &EvalArgs, input: &Expr, steps: &[Step], result: &Expr) -> String {
+    if args.quiet {
+        return format!("${}$", render::expr_to_latex(result));
+    }
+    if !args.trace {
+        return format!(
+            "${} \\twoheadrightarrow_\\beta {}$",
+            render::expr_to_latex(input),
+            render::expr_to_latex(result)
+        );
+    }
+    render::trace_to_latex(input, steps)
+}
+
+fn fmt_markdown(args: &EvalArgs, input: &Expr, steps: &[Step], result: &Expr) -> String {
+    if args.quiet {
+        return render::expr_to_markdown(result);
+    }
+    if !args.trace {
+        return format!(
+            "{} ↠β {}",
+            render::expr_to_markdown(input),
+            render::expr_to_markdown(result)
+        );
+    }
+    render::trace_to_markdown(input, steps)
+}
@@ -1,22 +1,32 @@
-use super::State;
+use super::{state, State};
 use anyhow::{anyhow, Result};
+use std::path::Path;
 
-pub fn run_file(file_name: String) -> Result<()> {
-    let mut state = State::new()?;
+pub fn run_file(file_name: String, config: Option<&Path>) -> Result<()> {
+    let mut state = State::new(config, Some(Path::new(&file_name)))?;
     if !std::path::Path::new(&file_name).exists() {
         return Err(anyhow!("File not found"));
     }
     let contents = std::fs::read_to_string(file_name)?;
-    let lines = contents.lines();
-    for line in lines {
+    let mut printed = 0;
+    for line in contents.lines() {
         state.exec(line.to_string());
+        // `:clear` empties the history, what was printed stays
+        printed = printed.min(state.history.len());
+        loop {
+            let running = state.wait(state::STEP_BATCH);
+            for line in &state.history[printed..] {
+                println!("{}", line);
+            }
+            printed = state.history.len();
+            if !running {
+                break;
+            }
+        }
         if state.exit {
             break;
         }
     }
 
-    let history = state.history.join("\n");
-    println!("{}", history);
-
     Ok(())
 }
@@ -1,30 +1,183 @@
-use super::State;
+use super::{
+    commands, config,
+    input_highlight::{self, is_name_char},
+    state, State,
+};
 use anyhow::Result;
-use std::io::{self, Write};
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::{CmdKind, Highlighter},
+    hint::{Hint, Hinter},
+    history::DefaultHistory,
+    validate::Validator,
+    Context, Editor, Helper,
+};
+use std::{borrow::Cow, path::Path, sync::atomic::Ordering};
 
-pub fn run_repl() -> Result<()> {
-    let mut state = State::new()?;
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
+// Entered lines, in the config folder
+const HISTORY_FILE: &str = "repl_history";
+
+pub fn run_repl(config: Option<&Path>) -> Result<()> {
+    let mut state = State::new(config, None)?;
+    let history_file = config::config_folder()?.join(HISTORY_FILE);
+    let mut editor = Editor::<ReplHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(ReplHelper {
+        names: Vec::new(),
+        color: state.config.use_color,
+    }));
+    if history_file.exists() {
+        editor.load_history(&history_file)?;
+    }
+    // Ctrl-C only reaches the handler while evaluating, the editor reads it as a key
+    let cancel = state.cancel.clone();
+    ctrlc::set_handler(move || cancel.store(true, Ordering::Relaxed))?;
 
     loop {
-        write!(stdout, "> ")?;
-        stdout.flush()?;
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            // Ctrl-C drops the line being typed, and cancels an evaluation in progress
+            Err(ReadlineError::Interrupted) => continue,
+            // Ctrl-D
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        };
 
-        let mut line = String::new();
-        if stdin.read_line(&mut line)? == 0 {
-            // EOF (Ctrl-D or Ctrl-Z)
+        let line = line.trim();
+        if !line.is_empty() {
+            editor.add_history_entry(line)?;
+        }
+        let mut printed = state.history.len();
+        state.exec(line.to_string());
+        if state.history.len() < printed {
+            editor.clear_screen()?;
+            printed = state.history.len();
+        }
+        // Steps are printed as the evaluation takes them
+        loop {
+            let running = state.wait(state::STEP_BATCH);
+            for line in state.history.iter().skip(printed) {
+                println!("{}", line);
+            }
+            printed = state.history.len();
+            if !running {
+                break;
+            }
+        }
+        // The line may have defined a name or changed `useColor`
+        if let Some(helper) = editor.helper_mut() {
+            helper.names = state.vm.definitions().keys().cloned().collect();
+            helper.color = state.config.use_color;
+        }
+        if state.exit {
             break;
         }
+    }
 
-        let line = line.trim();
-        if line == "exit" || line == "quit" {
-            break;
+    editor.save_history(&history_file)?;
+    Ok(())
+}
+
+// Completes commands at the start of the line and defined names anywhere, and
+// highlights the line as it is typed
+struct ReplHelper {
+    names: Vec<String>,
+    color: bool,
+}
+
+impl ReplHelper {
+    fn highlight_line(&self, line: &str, pos: usize) -> input_highlight::Highlighted {
+        input_highlight::highlight(line, pos, |name| self.names.iter().any(|n| n == name))
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        // Commands after the colon they start with
+        if line.starts_with(':') && !line[..pos].contains(char::is_whitespace) {
+            let candidates = commands::COMMANDS
+                .iter()
+                .filter(|command| command.name.starts_with(&line[1..pos]))
+                .map(|command| pair(command.name))
+                .collect();
+            return Ok((1, candidates));
         }
 
-        state.exec(line.to_string());
-        println!("{}", state.history.join("\n"));
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !is_name_char(*c))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let candidates = self
+            .names
+            .iter()
+            .filter(|name| name.starts_with(&line[start..pos]))
+            .map(|name| pair(name))
+            .collect();
+        Ok((start, candidates))
     }
+}
 
-    Ok(())
+fn pair(candidate: &str) -> Pair {
+    Pair {
+        display: candidate.to_string(),
+        replacement: candidate.to_string(),
+    }
+}
+
+// Problems with the line, shown after it but never inserted
+struct Problems(String);
+
+impl Hint for Problems {
+    fn display(&self) -> &str {
+        &self.0
+    }
+
+    fn completion(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = Problems;
+
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<Problems> {
+        let problems = self.highlight_line(line, pos).problems;
+        (pos == line.len() && !problems.is_empty())
+            .then(|| Problems(format!("  ({})", problems.join(", "))))
+    }
 }
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        if self.color {
+            Cow::Owned(self.highlight_line(line, pos).ansi)
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        if self.color {
+            Cow::Owned(format!("\x1b[38;5;240m{}\x1b[0m", hint))
+        } else {
+            Cow::Borrowed(hint)
+        }
+    }
+
+    // Moving the cursor changes the matching paren
+    fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        self.color
+    }
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
@@ -1,29 +1,78 @@
-use super::State;
+use super::{
+    ansi, config, debugger::Debugger, history_view::HistoryView, input_highlight,
+    line_editor::LineEditor, State,
+};
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::{io, thread, time::Duration};
+use rambda::vm::Control;
+use std::{
+    collections::VecDeque,
+    io,
+    path::Path,
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
 use tui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Direction, Layout},
-    style::Style,
-    widgets::{Block, BorderType, Borders, Paragraph},
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Paragraph, Wrap},
     Terminal,
 };
 
-pub fn run_tui() -> Result<()> {
+// Steps taken by Shift+Left / Shift+Right in the debugger
+const DEBUGGER_JUMP: usize = 10;
+// Lines scrolled per mouse wheel notch
+const WHEEL_LINES: usize = 3;
+// Oldest history lines are dropped past this
+const MAX_HISTORY_LINES: usize = 5_000;
+// Characters revealed per frame by the print effect
+const TYPED_PER_FRAME: usize = 2;
+// Progress messages of the worker handled per frame
+const STEPS_PER_FRAME: usize = 100;
+// Frames of the progress indicator, a new one every `SPINNER_FRAME`
+const SPINNER: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_ASCII: &[char] = &['|', '/', '-', '\\'];
+const SPINNER_FRAME: Duration = Duration::from_millis(80);
+// Reduction steps between looking for Esc while a command blocks the event loop
+const CANCEL_POLL_STEPS: usize = 500;
+// Submitted input lines, in the config folder
+const INPUT_HISTORY_FILE: &str = "tui_history";
+
+pub fn run_tui(config: Option<&Path>) -> Result<()> {
     terminal::enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     terminal.clear()?;
 
-    let mut input = String::new();
-    let mut state = State::new()?;
+    let mut input =
+        LineEditor::with_history_file(config::config_folder()?.join(INPUT_HISTORY_FILE))?;
+    let mut state = State::new(config, None)?;
+    if state.config.journal {
+        state.start_journal()?;
+    }
+    watch_for_cancel(&mut state);
+    // Lines entered while an evaluation runs, executed once it ends
+    let mut queued = VecDeque::new();
+    let started = Instant::now();
+    let mut view = HistoryView::new();
+    let mut no_match = false;
 
     loop {
         terminal.draw(|f| {
@@ -33,44 +82,180 @@ pub fn run_tui() -> Result<()> {
                 .constraints([Constraint::Min(5), Constraint::Length(3)].as_ref())
                 .split(size);
 
-            let log_content = Paragraph::new(state.displayed_history.join("\n"))
+            let log_area = if let Some(debugger) = &state.debugger {
+                let panes = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                    .split(chunks[0]);
+                f.render_widget(debugger_pane(&state, debugger), panes[1]);
+                panes[0]
+            } else {
+                chunks[0]
+            };
+            let log_lines = view.visible(
+                &state.displayed_history,
+                log_area.height.saturating_sub(2) as usize,
+            );
+            let log_title = if let Some((taken, size)) = state.progress() {
+                let spinner = if state.config.use_utf8 {
+                    SPINNER
+                } else {
+                    SPINNER_ASCII
+                };
+                let frame = started.elapsed().as_millis() / SPINNER_FRAME.as_millis();
+                format!(
+                    "History ({} evaluating: {} steps, size {}, Esc to cancel)",
+                    spinner[frame as usize % spinner.len()],
+                    taken,
+                    size
+                )
+            } else if view.is_following() {
+                "History".to_string()
+            } else {
+                "History (scrolled, Ctrl+End to follow)".to_string()
+            };
+            let log_content = Paragraph::new(log_lines)
                 .block(
                     Block::default()
                         .borders(Borders::ALL)
                         .border_type(BorderType::Rounded)
-                        .title("History"),
+                        .title(log_title),
                 )
                 .alignment(Alignment::Left);
-            f.render_widget(log_content, chunks[0]);
+            f.render_widget(log_content, log_area);
 
-            let input_text = Paragraph::new(input.as_str())
+            let (text, column, title) = match &view.search {
+                Some(query) => (
+                    Spans::from(query.clone()),
+                    query.chars().count(),
+                    Spans::from(if no_match {
+                        "Search (no match)"
+                    } else {
+                        "Search (Enter/↑ older, ↓ newer, Esc close)"
+                    }),
+                ),
+                None => {
+                    let highlighted =
+                        input_highlight::highlight(input.as_str(), input.cursor(), |name| {
+                            state.vm.definitions().contains_key(name)
+                        });
+                    let mut title = vec![Span::raw("Execute")];
+                    if !queued.is_empty() {
+                        title.push(Span::raw(format!(" ({} waiting)", queued.len())));
+                    }
+                    if !highlighted.problems.is_empty() {
+                        title.push(Span::styled(
+                            format!(" ({})", highlighted.problems.join(", ")),
+                            Style::default().fg(Color::Indexed(1)),
+                        ));
+                    }
+                    let text = if state.config.use_color {
+                        ansi::to_spans(&highlighted.ansi)
+                    } else {
+                        Spans::from(input.as_str().to_string())
+                    };
+                    (text, input.cursor_column(), Spans::from(title))
+                }
+            };
+            // Scroll long lines horizontally to keep the cursor inside the box
+            let width = chunks[1].width.saturating_sub(2).max(1) as usize;
+            let offset = column.saturating_sub(width - 1);
+            let input_text = Paragraph::new(text)
                 .style(Style::default())
                 .block(
                     Block::default()
                         .borders(Borders::ALL)
                         .border_type(BorderType::Rounded)
-                        .title("Execute"),
+                        .title(title),
                 )
-                .alignment(Alignment::Left);
+                .alignment(Alignment::Left)
+                .scroll((0, offset as u16));
             f.render_widget(input_text, chunks[1]);
+            f.set_cursor(chunks[1].x + 1 + (column - offset) as u16, chunks[1].y + 1);
         })?;
 
         if event::poll(Duration::from_millis(25))? {
-            if let event::Event::Key(KeyEvent { code, .. }) = event::read()? {
-                match code {
-                    KeyCode::Enter => {
-                        state.exec(input.clone());
-                        input.clear();
+            let total = state.displayed_history.len();
+            match event::read()? {
+                event::Event::Paste(text) if view.search.is_some() => view.edit_search(|query| {
+                    query.push_str(&text.lines().collect::<Vec<_>>().join(" "))
+                }),
+                event::Event::Paste(text) => input.paste(&text),
+                event::Event::Mouse(MouseEvent { kind, .. }) => match kind {
+                    MouseEventKind::ScrollUp => view.scroll_up(WHEEL_LINES, total),
+                    MouseEventKind::ScrollDown => view.scroll_down(WHEEL_LINES, total),
+                    _ => {}
+                },
+                event::Event::Key(KeyEvent { code, .. }) if view.search.is_some() => match code {
+                    KeyCode::Enter | KeyCode::Up => {
+                        no_match = !view.find(&state.displayed_history, false)
+                    }
+                    KeyCode::Down => no_match = !view.find(&state.displayed_history, true),
+                    KeyCode::Esc => {
+                        view.close_search();
+                        no_match = false;
                     }
                     KeyCode::Backspace => {
-                        input.pop();
+                        view.edit_search(|query| {
+                            query.pop();
+                        });
+                        no_match = false;
                     }
-                    KeyCode::Esc => break,
                     KeyCode::Char(c) => {
-                        input.push(c);
+                        view.edit_search(|query| query.push(c));
+                        no_match = false;
                     }
                     _ => {}
+                },
+                event::Event::Key(KeyEvent {
+                    code, modifiers, ..
+                }) => {
+                    // With an empty input the arrow keys drive the debugger
+                    let debugging = state.debugger.is_some() && input.is_empty();
+                    let shift = modifiers.contains(KeyModifiers::SHIFT);
+                    let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+                    match code {
+                        KeyCode::Right if debugging && shift => {
+                            state.exec(format!(":step {}", DEBUGGER_JUMP))
+                        }
+                        KeyCode::Left if debugging && shift => {
+                            state.exec(format!(":back {}", DEBUGGER_JUMP))
+                        }
+                        KeyCode::Right if debugging => state.exec(":step".to_string()),
+                        KeyCode::Left if debugging => state.exec(":back".to_string()),
+                        KeyCode::End if debugging && !ctrl => state.exec(":run".to_string()),
+                        KeyCode::Esc if state.is_running() => {
+                            state.cancel.store(true, Ordering::Relaxed)
+                        }
+                        KeyCode::Esc if state.debugger.is_some() => {
+                            state.exec(":close".to_string())
+                        }
+                        KeyCode::PageUp => view.scroll_up(view.page(), total),
+                        KeyCode::PageDown => view.scroll_down(view.page(), total),
+                        KeyCode::Up if shift => view.scroll_up(1, total),
+                        KeyCode::Down if shift => view.scroll_down(1, total),
+                        KeyCode::Home if ctrl => view.scroll_to_top(),
+                        KeyCode::End if ctrl => view.follow(),
+                        KeyCode::Char('f') if ctrl => view.open_search(),
+                        KeyCode::Enter => {
+                            let (line, persisted) = input.submit();
+                            if let Err(err) = persisted {
+                                state
+                                    .history
+                                    .push(format!("Error: Could not save the history: {}", err));
+                            }
+                            if state.is_running() {
+                                queued.push_back(line);
+                            } else {
+                                state.exec(line);
+                            }
+                            view.follow();
+                        }
+                        KeyCode::Esc => break,
+                        _ => edit_line(&mut input, code, modifiers),
+                    }
                 }
+                _ => {}
             }
         }
 
@@ -78,23 +263,13 @@ pub fn run_tui() -> Result<()> {
             break;
         }
 
-        for _ in 1..3 {
-            for (i, line) in state.history.iter().enumerate() {
-                let displayed_line = state.displayed_history.get(i);
-                if displayed_line.is_none() {
-                    state.displayed_history.push(String::new());
-                } else {
-                    let displayed_line = displayed_line.unwrap();
-                    if displayed_line.len() < line.len() {
-                        let char_to_push = line.chars().nth(displayed_line.len());
-                        if let Some(char_to_push) = char_to_push {
-                            state.displayed_history[i].push(char_to_push);
-                            break;
-                        }
-                    }
-                }
-            }
+        if !state.advance(STEPS_PER_FRAME)
+            && let Some(line) = queued.pop_front()
+        {
+            state.exec(line);
         }
+        trim_history(&mut state, &mut view);
+        advance_typing(&mut state);
 
         thread::sleep(Duration::from_millis(10));
     }
@@ -103,9 +278,167 @@ pub fn run_tui() -> Result<()> {
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
+    state.end_journal()?;
 
     Ok(())
 }
+
+// Commands reducing on the event loop, such as `:diagram --steps`, look for Esc every
+// few steps, and so does waiting for the worker in the middle of a command
+fn watch_for_cancel(state: &mut State) {
+    let cancel = state.cancel.clone();
+    let mut since_poll = 0;
+    state.vm.before_step(move |_| {
+        since_poll += 1;
+        if since_poll >= CANCEL_POLL_STEPS {
+            since_poll = 0;
+            if esc_pressed() {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        }
+        Control::Continue
+    });
+    state.check_cancel = Some(esc_pressed);
+}
+
+// Only called while the event loop is blocked, other keys pressed meanwhile are dropped
+fn esc_pressed() -> bool {
+    let mut pressed = false;
+    while event::poll(Duration::ZERO).unwrap_or(false) {
+        if let Ok(Event::Key(KeyEvent {
+            code: KeyCode::Esc, ..
+        })) = event::read()
+        {
+            pressed = true;
+        }
+    }
+    pressed
+}
+
+// Keys of the Execute box, word-wise variants on Ctrl or Alt
+fn edit_line(input: &mut LineEditor, code: KeyCode, modifiers: KeyModifiers) {
+    let word = modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+    let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+    let alt = modifiers.contains(KeyModifiers::ALT);
+    match code {
+        KeyCode::Left if word => input.word_left(),
+        KeyCode::Right if word => input.word_right(),
+        KeyCode::Backspace if word => input.delete_word_back(),
+        KeyCode::Delete if word => input.delete_word_forward(),
+        KeyCode::Char('w') if ctrl => input.delete_word_back(),
+        KeyCode::Char('a') if ctrl => input.home(),
+        KeyCode::Char('e') if ctrl => input.end(),
+        KeyCode::Char('u') if ctrl => input.delete_to_start(),
+        KeyCode::Char('k') if ctrl => input.delete_to_end(),
+        KeyCode::Char('b') if alt => input.word_left(),
+        KeyCode::Char('f') if alt => input.word_right(),
+        KeyCode::Char('d') if alt => input.delete_word_forward(),
+        KeyCode::Left => input.left(),
+        KeyCode::Right => input.right(),
+        KeyCode::Home => input.home(),
+        KeyCode::End => input.end(),
+        KeyCode::Up => input.history_prev(),
+        KeyCode::Down => input.history_next(),
+        KeyCode::Backspace => input.backspace(),
+        KeyCode::Delete => input.delete(),
+        KeyCode::Char(c) if !ctrl => input.insert(c),
+        _ => {}
+    }
+}
+
+// Drops the oldest lines once the history outgrows `MAX_HISTORY_LINES`
+fn trim_history(state: &mut State, view: &mut HistoryView) {
+    let excess = state.history.len().saturating_sub(MAX_HISTORY_LINES);
+    if excess == 0 {
+        return;
+    }
+    state.history.drain(..excess);
+    let displayed = excess.min(state.displayed_history.len());
+    state.displayed_history.drain(..displayed);
+    view.trimmed(excess);
+}
+
+// Copies the history into the displayed history, a few characters per frame when the
+// print effect is on. Escape sequences are copied whole.
+fn advance_typing(state: &mut State) {
+    state.displayed_history.truncate(state.history.len());
+    if !state.config.print_effect {
+        state.displayed_history.clone_from(&state.history);
+        return;
+    }
+
+    for _ in 0..TYPED_PER_FRAME {
+        let Some(i) = (0..state.history.len()).find(|&i| {
+            state
+                .displayed_history
+                .get(i)
+                .is_none_or(|displayed| displayed.len() < state.history[i].len())
+        }) else {
+            break;
+        };
+        if i == state.displayed_history.len() {
+            state.displayed_history.push(String::new());
+        }
+
+        let displayed = &mut state.displayed_history[i];
+        let mut rest = state.history[i][displayed.len()..].chars();
+        match rest.next() {
+            Some('\x1b') => {
+                displayed.push('\x1b');
+                for c in rest.by_ref() {
+                    displayed.push(c);
+                    if c == 'm' {
+                        break;
+                    }
+                }
+            }
+            Some(c) => displayed.push(c),
+            None => {}
+        }
+    }
+}
+
+fn debugger_pane<'a>(state: &State, debugger: &Debugger) -> Paragraph<'a> {
+    let breakpoints = debugger
+        .breakpoints
+        .iter()
+        .map(|breakpoint| breakpoint.describe())
+        .collect::<Vec<_>>();
+    let mut lines = vec![
+        Spans::from(format!(
+            "Step {}   Size {}   Strategy {}",
+            debugger.position(),
+            debugger.current().size(),
+            state.vm.strategy()
+        )),
+        Spans::from(format!(
+            "Breakpoints: {}",
+            if breakpoints.is_empty() {
+                "none".to_string()
+            } else {
+                breakpoints.join(", ")
+            }
+        )),
+        Spans::from(debugger.message.clone().unwrap_or_default()),
+        Spans::from(""),
+        ansi::to_spans(&state.fmt_debugger_expr(debugger)),
+        Spans::from(""),
+    ];
+    lines.push(Spans::from(Span::styled(
+        "→ step  ← back  Shift+→/← ±10  End run  Esc close",
+        Style::default().fg(Color::Indexed(240)),
+    )));
+
+    Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title("Debugger"),
+        )
+        .wrap(Wrap { trim: false })
+}
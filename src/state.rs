@@ -1,37 +1,40 @@
+use crate::{
+    commands,
+    config::{config_folder, Config},
+    debugger::Debugger,
+    worker::{self, Job, Progress},
+};
 use anyhow::Result;
-use rambda::vm::Vm;
-use serde::{Deserialize, Serialize};
-
-const CONFIG_FOLDER: &str = "rambda";
-const CONFIG_FILE: &str = "config.yaml";
-const DEFAULT_CONFIG: &[u8] = include_bytes!("../default_config.yaml");
-
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Config {
-    pub use_utf8: bool,
-    pub use_color: bool,
-    pub merge_args: bool,
-    pub magic: Option<char>,
-    pub print_effect: bool,
-}
+use rambda::{
+    ast::{Expr, Path, RedType},
+    render,
+    vm::{Stats, Step, Termination, Vm},
+};
+use std::{
+    fs::File,
+    io::Write,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, RecvTimeoutError, TryRecvError},
+        Arc,
+    },
+    time::Duration,
+};
 
-impl Config {
-    pub fn new() -> Result<Self> {
-        let config_folder = dirs::config_dir()
-            .ok_or(anyhow::anyhow!("Failed to get config directory"))?
-            .join(CONFIG_FOLDER);
-        if !config_folder.exists() {
-            std::fs::create_dir_all(&config_folder)?;
-        }
-        let config_file = config_folder.join(CONFIG_FILE);
-        if !config_file.exists() {
-            std::fs::write(config_file.clone(), DEFAULT_CONFIG)?;
-        }
-        let config: Config = serde_yaml::from_reader(std::fs::File::open(config_file)?)?;
-        Ok(config)
-    }
-}
+const REDEX_HIGHLIGHT: &str = "\x1b[48;5;52m";
+const CONTRACTUM_HIGHLIGHT: &str = "\x1b[48;5;22m";
+/// Progress messages read at a time when an evaluation runs to its end, runners print
+/// in between.
+pub const STEP_BATCH: usize = 1000;
+// Terms deeper than this end an evaluation, within what the threads' stacks can reduce
+// and print
+const MAX_DEPTH: usize = 10_000;
+// How often a session waiting for its worker checks `check_cancel`
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+const JOURNAL_FILE: &str = "journal.rc";
+// Where the journal of a session that did not exit cleanly is kept for `:restore`
+const RECOVERED_FILE: &str = "recovered.rc";
 
 pub struct State {
     pub displayed_history: Vec<String>,
@@ -39,68 +42,505 @@ pub struct State {
     pub exit: bool,
     pub vm: Vm,
     pub config: Config,
+    pub debugger: Option<Debugger>,
+    /// Definitions and commands that succeeded, as typed, for `:save`.
+    pub session: Vec<String>,
+    // Depth of the `:load`s running, their lines are not recorded on their own
+    pub replaying: usize,
+    journal: Option<(File, PathBuf)>,
+    running: Option<Running>,
+    /// Set to cancel the evaluation in progress, from a signal handler or the event loop.
+    pub cancel: Arc<AtomicBool>,
+    /// Asked now and then while waiting for an evaluation, cancels it when true.
+    pub check_cancel: Option<fn() -> bool>,
+    /// Statistics of the last evaluation that ended, for `:stats`.
+    pub last_stats: Option<Stats>,
+}
+
+/// What an evaluation prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Report {
+    /// Every step as it is taken, for `eval`.
+    Trace,
+    /// The result and the step count, for `:steps`.
+    Count,
+    /// The result and the statistics, for `:stats`.
+    Stats,
+    /// Nothing while it runs, the steps are kept for the callback of `start_collect`.
+    Collect,
+    /// The lines of a job as they come, for `start_job`.
+    Lines,
+}
+
+/// Gets the steps of an evaluation started by `start_collect` once it reached a normal
+/// form.
+pub type Collected = Box<dyn FnOnce(&mut State, Vec<Step>) -> Result<()>>;
+
+/// Runs once a job started by `start_job_then` finished, with or without an error.
+pub type AfterJob = Box<dyn FnOnce(&mut State) -> Result<()>>;
+
+// An evaluation on the worker thread. Traced steps are printed as they come, the last
+// term is held back until the step after it is known, so its redex can be highlighted.
+struct Running {
+    receiver: Receiver<Progress>,
+    report: Report,
+    last: Expr,
+    // `None` for the input
+    red_type: Option<RedType>,
+    contractum: Option<Path>,
+    taken: usize,
+    size: usize,
+    // The steps so far and what to do with them, for `Report::Collect`
+    steps: Vec<Step>,
+    then: Option<Collected>,
+    after_job: Option<AfterJob>,
 }
 
 impl State {
-    pub fn new() -> Result<Self> {
-        let config = Config::new()?;
+    /// Starts a session with the config at `config_path`, or the user's one, under the
+    /// project config of `script` or the working directory.
+    pub fn new(
+        config_path: Option<&std::path::Path>,
+        script: Option<&std::path::Path>,
+    ) -> Result<Self> {
+        let config = Config::load(config_path, script)?;
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut vm = Vm::new();
+        vm.set_cancel_flag(cancel.clone());
+        vm.set_max_depth(Some(MAX_DEPTH));
 
         Ok(Self {
             displayed_history: Vec::new(),
             history: Vec::new(),
             exit: false,
-            vm: Vm::new(),
+            vm,
             config,
+            debugger: None,
+            session: Vec::new(),
+            replaying: 0,
+            journal: None,
+            running: None,
+            cancel,
+            check_cancel: None,
+            last_stats: None,
         })
     }
 
-    pub fn exec(&mut self, input: String) {
-        let parts = input.split_whitespace().collect::<Vec<_>>();
-        let command = parts[0];
-        let args: Vec<String> = parts[1..].iter().map(|&s| s.to_string()).collect();
-        if args.len() >= 2 && args[0] == ":=" {
-            let body = args[1..].join(" ");
-            self.history.push(format!("{} := {}", command, body));
-            return;
+    /// Evaluates `expr` on a worker thread, `advance` prints what `report` asks for as
+    /// it comes.
+    pub fn start_eval(&mut self, expr: Expr, report: Report) {
+        self.spawn(expr, report, None);
+    }
+
+    /// Evaluates `expr` on a worker thread like `start_eval`, handing every step to
+    /// `then` at the end, for commands that render the whole reduction.
+    pub fn start_collect(&mut self, expr: Expr, then: Collected) {
+        self.spawn(expr, Report::Collect, Some(then));
+    }
+
+    /// Runs `job` about `expr` on a worker thread with a fork of the vm, `advance` prints
+    /// its lines as they come. It stops at the limits and the cancel flag of the vm.
+    pub fn start_job(&mut self, expr: Expr, job: Job) {
+        self.finish();
+        self.cancel.store(false, Ordering::Relaxed);
+        let receiver = worker::spawn_job(self.vm.fork(), job);
+        self.track(receiver, expr, Report::Lines, None);
+    }
+
+    /// Runs `job` like `start_job`, then `after` back in the session.
+    pub fn start_job_then(&mut self, expr: Expr, job: Job, after: AfterJob) {
+        self.start_job(expr, job);
+        if let Some(running) = &mut self.running {
+            running.after_job = Some(after);
         }
-        match command {
-            "clear" => {
-                self.history.clear();
-            }
-            "exit" => {
-                self.exit = true;
-                self.history.push("Exiting...".to_string());
-            }
-            "eval" => {
-                let expr = args.join(" ");
-                if let Err(e) = self.vm.parse_expr(&expr) {
-                    self.history.push(format!("Error: {}", e));
+    }
+
+    fn spawn(&mut self, expr: Expr, report: Report, then: Option<Collected>) {
+        self.finish();
+        self.cancel.store(false, Ordering::Relaxed);
+        let trace = matches!(report, Report::Trace | Report::Collect);
+        let receiver = worker::spawn(self.vm.fork(), expr.clone(), trace);
+        self.track(receiver, expr, report, then);
+    }
+
+    fn track(
+        &mut self,
+        receiver: Receiver<Progress>,
+        expr: Expr,
+        report: Report,
+        then: Option<Collected>,
+    ) {
+        self.running = Some(Running {
+            receiver,
+            report,
+            size: expr.size(),
+            last: expr,
+            red_type: None,
+            contractum: None,
+            taken: 0,
+            steps: Vec::new(),
+            then,
+            after_job: None,
+        });
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Steps taken so far and the size of the current term, while evaluating.
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        self.running
+            .as_ref()
+            .map(|running| (running.taken, running.size))
+    }
+
+    /// Handles up to `budget` messages the worker already sent, returns whether the
+    /// evaluation still runs.
+    pub fn advance(&mut self, budget: usize) -> bool {
+        self.receive(budget, false)
+    }
+
+    /// Like `advance`, but waits for the worker to send something.
+    pub fn wait(&mut self, budget: usize) -> bool {
+        self.receive(budget, true)
+    }
+
+    /// Runs the evaluation in progress to its end.
+    pub fn finish(&mut self) {
+        while self.wait(STEP_BATCH) {}
+    }
+
+    fn receive(&mut self, budget: usize, block: bool) -> bool {
+        let Some(mut running) = self.running.take() else {
+            return false;
+        };
+        for i in 0..budget {
+            let message = if block && i == 0 {
+                self.wait_for(&running.receiver)
+            } else {
+                match running.receiver.try_recv() {
+                    Ok(message) => Ok(message),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => Err(()),
                 }
-                self.history.push(expr);
-                match self.vm.eval() {
-                    Ok(steps) => {
-                        for (red_type, expr) in steps {
-                            self.history.push(format!(
-                                "  {} {}",
-                                red_type
-                                    .fmt_with_config(self.config.use_color, self.config.use_utf8),
-                                expr.fmt_with_config(
-                                    self.config.use_color,
-                                    self.config.use_utf8,
-                                    self.config.merge_args
-                                ),
-                            ));
-                        }
+            };
+            match message {
+                Ok(Progress::Step(step)) if running.report == Report::Collect => {
+                    running.taken += 1;
+                    running.size = step.expr.size();
+                    running.steps.push(step);
+                }
+                Ok(Progress::Step(step)) => {
+                    self.history.push(self.fmt_running(&running, Some(&step)));
+                    running.taken += 1;
+                    running.size = step.expr.size();
+                    running.last = step.expr;
+                    running.red_type = Some(step.red_type);
+                    running.contractum = step.redex;
+                }
+                Ok(Progress::Line(line)) => self.history.push(line),
+                Ok(Progress::Finished(result)) => {
+                    // A job ends once cancelled, the flag is not needed any more
+                    self.cancel.store(false, Ordering::Relaxed);
+                    if let Err(err) = result {
+                        self.history.push(format!("Error: {}", err));
                     }
-                    Err(err) => {
+                    if let Some(after) = running.after_job.take()
+                        && let Err(err) = after(self)
+                    {
                         self.history.push(format!("Error: {}", err));
                     }
+                    return false;
+                }
+                Ok(Progress::Count { taken, size }) => {
+                    running.taken = taken;
+                    running.size = size;
+                }
+                Ok(Progress::Done {
+                    termination,
+                    taken,
+                    result,
+                    stats,
+                }) => {
+                    self.end(running, termination, taken, result, stats);
+                    return false;
+                }
+                Err(()) => {
+                    self.history
+                        .push("Error: the evaluation stopped unexpectedly".to_string());
+                    return false;
                 }
-                self.history.push(String::new());
             }
-            _ => {
-                self.history.push(format!("Unknown command: {}", command));
+        }
+        self.running = Some(running);
+        true
+    }
+
+    // Blocks until the next message, checking for a cancel meanwhile
+    fn wait_for(&self, receiver: &Receiver<Progress>) -> Result<Progress, ()> {
+        loop {
+            match receiver.recv_timeout(CANCEL_CHECK_INTERVAL) {
+                Ok(message) => return Ok(message),
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.check_cancel.is_some_and(|check| check()) {
+                        self.cancel.store(true, Ordering::Relaxed);
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(()),
+            }
+        }
+    }
+
+    fn end(
+        &mut self,
+        mut running: Running,
+        termination: Termination,
+        taken: usize,
+        result: Expr,
+        stats: Stats,
+    ) {
+        if termination == Termination::Cancelled {
+            self.cancel.store(false, Ordering::Relaxed);
+        }
+        let fmt_result = self.fmt_expr(&result);
+        self.vm.set_expr(result);
+        if running.report == Report::Collect
+            && termination == Termination::NormalForm
+            && let Some(then) = running.then.take()
+        {
+            if let Err(err) = then(self, std::mem::take(&mut running.steps)) {
+                self.history.push(format!("Error: {}", err));
             }
+        } else if running.report == Report::Trace {
+            self.history.push(self.fmt_running(&running, None));
+            if termination == Termination::Cancelled {
+                self.history
+                    .push(format!("Cancelled after {} steps", taken));
+            }
+            self.history.push(String::new());
+        } else {
+            let line = match termination {
+                Termination::NormalForm => format!("=> {} ({} steps)", fmt_result, taken),
+                Termination::Cancelled => {
+                    format!("Cancelled after {} steps, stopped at {}", taken, fmt_result)
+                }
+                Termination::SizeLimit | Termination::DepthLimit => format!(
+                    "The term grew too large after {} steps, stopped at {}",
+                    taken, fmt_result
+                ),
+                _ => format!(
+                    "No normal form within {} steps, stopped at {}",
+                    taken, fmt_result
+                ),
+            };
+            self.history.push(line);
+        }
+        if running.report == Report::Stats {
+            self.history
+                .extend(stats.to_string().lines().map(|line| format!("  {}", line)));
+        }
+        self.last_stats = Some(stats);
+    }
+
+    fn fmt_running(&self, running: &Running, next: Option<&Step>) -> String {
+        let expr = self.fmt_step_expr(&running.last, running.contractum.as_ref(), next);
+        match &running.red_type {
+            Some(red_type) => format!(
+                "  {} {}",
+                red_type.fmt_with_config(self.config.use_color, self.config.use_utf8),
+                expr
+            ),
+            None => expr,
+        }
+    }
+
+    /// Keeps a copy of the session in the config folder until `end_journal`. A journal
+    /// left by a session that did not exit cleanly is moved aside to be restored.
+    pub fn start_journal(&mut self) -> Result<()> {
+        let folder = config_folder()?;
+        let path = folder.join(JOURNAL_FILE);
+        if std::fs::metadata(&path).is_ok_and(|meta| meta.len() > 0) {
+            let recovered = folder.join(RECOVERED_FILE);
+            std::fs::rename(&path, &recovered)?;
+            self.history.push(format!(
+                "The last session did not exit cleanly, bring it back with :restore {}",
+                recovered.display()
+            ));
+        }
+        self.journal = Some((File::create(&path)?, path));
+        Ok(())
+    }
+
+    /// Removes the journal, the session exited cleanly.
+    pub fn end_journal(&mut self) -> Result<()> {
+        if let Some((_, path)) = self.journal.take() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Forgets the definitions, the debugger and the recorded session, keeping the
+    /// strategy and the step limit.
+    pub fn reset_session(&mut self) {
+        self.vm.clear_definitions();
+        self.debugger = None;
+        self.session.clear();
+        if let Some((file, _)) = &mut self.journal
+            && let Err(err) = file.set_len(0)
+        {
+            self.history.push(format!("Error: journal: {}", err));
+        }
+    }
+
+    fn record(&mut self, input: &str) {
+        if self.replaying > 0 {
+            return;
+        }
+        self.session.push(input.to_string());
+        if let Some((file, _)) = &mut self.journal
+            && let Err(err) = writeln!(file, "{}", input)
+        {
+            self.history.push(format!("Error: journal: {}", err));
+            self.journal = None;
+        }
+    }
+
+    pub fn exec(&mut self, input: String) {
+        self.finish();
+        // A cancel that came after the last evaluation ended is stale
+        self.cancel.store(false, Ordering::Relaxed);
+        let input = input.trim();
+        // Scripts may contain comments
+        if input.is_empty() || input.starts_with('#') {
+            return;
+        }
+        let parts = input.split_whitespace().collect::<Vec<_>>();
+        if let [name, ":=", body @ ..] = parts.as_slice()
+            && !body.is_empty()
+        {
+            let body = body.join(" ");
+            match self.vm.define(name, &body) {
+                Ok(()) => {
+                    self.history.push(format!("{} := {}", name, body));
+                    self.record(input);
+                }
+                Err(e) => self.history.push(format!("Error: {}", e)),
+            }
+            return;
+        }
+
+        let args = parts[1..]
+            .iter()
+            .map(|&s| s.to_string())
+            .collect::<Vec<_>>();
+        let (command, args) = match parts[0].strip_prefix(':') {
+            Some(name) => match commands::find(name) {
+                Some(command) => (command, args),
+                None => {
+                    self.history
+                        .push(format!("Unknown command: :{}, see :help", name));
+                    return;
+                }
+            },
+            None => {
+                match commands::find_bare(parts[0], |name| self.vm.definitions().contains_key(name))
+                {
+                    Some(command) => (command, args),
+                    None => {
+                        let args = parts.iter().map(|&s| s.to_string()).collect::<Vec<_>>();
+                        (commands::find("eval").unwrap(), args)
+                    }
+                }
+            }
+        };
+        if command.exec(self, &args) && command.record {
+            self.record(input);
+        }
+    }
+
+    pub fn fmt_expr(&self, expr: &Expr) -> String {
+        expr.fmt_with_config(
+            self.config.use_color,
+            self.config.use_utf8,
+            self.config.merge_args,
+        )
+    }
+
+    pub fn push_debugger_status(&mut self) {
+        let Some(debugger) = &self.debugger else {
+            return;
+        };
+        let trace = debugger.trace();
+        let red_type = trace.last().map_or(String::new(), |step| {
+            step.red_type
+                .fmt_with_config(self.config.use_color, self.config.use_utf8)
+        });
+        let line = format!(
+            "[{}] {} {}",
+            debugger.position(),
+            red_type,
+            self.fmt_debugger_expr(debugger)
+        );
+        let message = debugger.message.clone();
+        self.history.push(line);
+        if let Some(message) = message {
+            self.history.push(format!("  {}", message));
+        }
+    }
+
+    /// Current term of the debugger with its next redex highlighted.
+    pub fn fmt_debugger_expr(&self, debugger: &Debugger) -> String {
+        let highlights = debugger
+            .next_redex()
+            .map(|path| vec![(path.as_slice(), REDEX_HIGHLIGHT)])
+            .unwrap_or_default();
+        debugger.current().fmt_highlighted(
+            self.config.use_color,
+            self.config.use_utf8,
+            self.config.merge_args,
+            &highlights,
+        )
+    }
+
+    /// Highlights the contractum `expr` was produced with and the redex the next step contracts.
+    pub fn fmt_step_expr(
+        &self,
+        expr: &Expr,
+        contractum: Option<&Path>,
+        next: Option<&Step>,
+    ) -> String {
+        let mut highlights = Vec::new();
+        if let Some(path) = contractum {
+            highlights.push((path.as_slice(), CONTRACTUM_HIGHLIGHT));
+        }
+        if let Some(path) = next.and_then(|step| step.redex.as_ref()) {
+            highlights.push((path.as_slice(), REDEX_HIGHLIGHT));
+        }
+        expr.fmt_highlighted(
+            self.config.use_color,
+            self.config.use_utf8,
+            self.config.merge_args,
+            &highlights,
+        )
+    }
+
+    /// Writes a single frame to `path`, several ones to numbered files inside it.
+    pub fn write_diagrams(&mut self, path: &str, frames: &[Expr]) -> Result<()> {
+        if let [frame] = frames {
+            std::fs::write(path, render::expr_to_diagram_svg(frame))?;
+            self.history.push(format!("Wrote {}", path));
+            return Ok(());
+        }
+
+        let dir = std::path::Path::new(path);
+        std::fs::create_dir_all(dir)?;
+        for (i, frame) in frames.iter().enumerate() {
+            let file = dir.join(format!("step_{:04}.svg", i));
+            std::fs::write(&file, render::expr_to_diagram_svg(frame))?;
         }
+        self.history
+            .push(format!("Wrote {} frames to {}", frames.len(), path));
+        Ok(())
     }
 }
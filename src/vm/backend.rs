@@ -0,0 +1,59 @@
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    // Rewrites the term one redex at a time, following the strategy
+    #[default]
+    Substitution,
+
+    // Evaluates closed terms to weak head normal form with environments and closures
+    Krivine,
+
+    // Evaluates closed terms by value to an abstraction, with a continuation
+    Cek,
+
+    // Compiles closed terms to code for a Krivine machine and runs it
+    Bytecode,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 4] = [
+        Backend::Substitution,
+        Backend::Krivine,
+        Backend::Cek,
+        Backend::Bytecode,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Substitution => "substitution",
+            Backend::Krivine => "krivine",
+            Backend::Cek => "cek",
+            Backend::Bytecode => "bytecode",
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "substitution" => Ok(Backend::Substitution),
+            "krivine" => Ok(Backend::Krivine),
+            "cek" => Ok(Backend::Cek),
+            "bytecode" => Ok(Backend::Bytecode),
+            _ => Err(format!(
+                "unknown backend '{}', expected one of: {}",
+                s,
+                Backend::ALL.map(|s| s.name()).join(", ")
+            )),
+        }
+    }
+}
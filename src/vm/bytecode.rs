@@ -0,0 +1,303 @@
+use super::{
+    machine::{Machine, Stop},
+    MachineRun, Termination,
+};
+use crate::ast::{DeBruijn, Definitions, Expr};
+use anyhow::{anyhow, Result};
+use std::{collections::HashMap, fmt, rc::Rc};
+
+/// An instruction of the bytecode. It runs on a Krivine machine with an accumulator
+/// holding the last loaded closure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instr {
+    /// Loads the closure at this de Bruijn index of the environment.
+    Access(usize),
+    /// Loads the code of a definition with an empty environment, a δ step.
+    Global(usize),
+    /// Loads the code at this address with the current environment.
+    Closure(usize),
+    /// Pushes the loaded closure as an argument.
+    Push,
+    /// Continues with the code and environment of the loaded closure.
+    Apply,
+    /// Binds the argument on top of the stack, a β step. With no argument left the
+    /// machine stops, the code from here in the current environment is the value.
+    Grab(String),
+}
+
+/// The code of a closed term and of the definitions it uses, see `Vm::compile`. Its
+/// `Display` is the disassembly.
+#[derive(Debug, Clone)]
+pub struct Program {
+    term: DeBruijn,
+    code: Vec<Instr>,
+    // Names and entry addresses of the definitions, by `Instr::Global` operand
+    globals: Vec<(String, usize)>,
+}
+
+impl Program {
+    pub(crate) fn compile(expr: &Expr, definitions: &Definitions) -> Result<Program> {
+        let mut compiler = Compiler {
+            definitions,
+            code: Vec::new(),
+            globals: Vec::new(),
+            indices: HashMap::new(),
+        };
+        let term = DeBruijn::from(expr);
+        compiler.block(&term)?;
+        // Definitions found while compiling may use more of them
+        let mut compiled = 0;
+        while let Some((name, _)) = compiler.globals.get(compiled) {
+            let definition = DeBruijn::from(&definitions[name.as_str()]);
+            compiler.globals[compiled].1 = compiler.block(&definition)?;
+            compiled += 1;
+        }
+
+        Ok(Program {
+            term,
+            code: compiler.code,
+            globals: compiler.globals,
+        })
+    }
+
+    /// The term compiled, with de Bruijn indices.
+    pub fn term(&self) -> &DeBruijn {
+        &self.term
+    }
+
+    pub fn code(&self) -> &[Instr] {
+        &self.code
+    }
+
+    // The term the code at `pc` was compiled from, with the closures of `env` in place
+    // of the variables it binds
+    fn decompile(&self, pc: usize, env: &Env, depth: usize) -> DeBruijn {
+        let access = |index: usize| match index.checked_sub(depth) {
+            // The closures are closed, nothing in them needs shifting
+            Some(index) => {
+                let closure = lookup(env, index);
+                self.decompile(closure.pc, &closure.env, 0)
+            }
+            None => DeBruijn::Var(index),
+        };
+        match (&self.code[pc], self.code.get(pc + 1)) {
+            (Instr::Grab(param), _) => DeBruijn::Abs(
+                param.clone(),
+                Box::new(self.decompile(pc + 1, env, depth + 1)),
+            ),
+            (Instr::Access(index), Some(Instr::Push)) => DeBruijn::Apl(
+                Box::new(self.decompile(pc + 2, env, depth)),
+                Box::new(access(*index)),
+            ),
+            (Instr::Closure(arg), _) => DeBruijn::Apl(
+                Box::new(self.decompile(pc + 2, env, depth)),
+                Box::new(self.decompile(*arg, env, depth)),
+            ),
+            (Instr::Access(index), _) => access(*index),
+            (Instr::Global(global), _) => DeBruijn::Free(self.globals[*global].0.clone()),
+            (Instr::Push | Instr::Apply, _) => unreachable!("blocks start with a load or a grab"),
+        }
+    }
+}
+
+// Emits each term as a block: its binders grab, its arguments are pushed from the last,
+// and the head is loaded and applied. Arguments other than variables get blocks of
+// their own after it
+struct Compiler<'a> {
+    definitions: &'a Definitions,
+    code: Vec<Instr>,
+    globals: Vec<(String, usize)>,
+    indices: HashMap<String, usize>,
+}
+
+impl Compiler<'_> {
+    fn block(&mut self, term: &DeBruijn) -> Result<usize> {
+        let start = self.code.len();
+        let mut args = Vec::new();
+        let mut term = term;
+        loop {
+            match term {
+                DeBruijn::Abs(param, body) => {
+                    self.code.push(Instr::Grab(param.clone()));
+                    term = body;
+                }
+                DeBruijn::Apl(func, arg) => {
+                    match **arg {
+                        // The closure it is bound to is pushed as it is
+                        DeBruijn::Var(index) => self.code.push(Instr::Access(index)),
+                        _ => {
+                            args.push((self.code.len(), &**arg));
+                            self.code.push(Instr::Closure(0));
+                        }
+                    }
+                    self.code.push(Instr::Push);
+                    term = func;
+                }
+                DeBruijn::Var(index) => {
+                    self.code.push(Instr::Access(*index));
+                    break;
+                }
+                DeBruijn::Free(name) => {
+                    let global = self.global(name)?;
+                    self.code.push(Instr::Global(global));
+                    break;
+                }
+            }
+        }
+        self.code.push(Instr::Apply);
+
+        for (at, arg) in args {
+            self.code[at] = Instr::Closure(self.block(arg)?);
+        }
+        Ok(start)
+    }
+
+    fn global(&mut self, name: &str) -> Result<usize> {
+        if let Some(&global) = self.indices.get(name) {
+            return Ok(global);
+        }
+        if !self.definitions.contains_key(name) {
+            return Err(anyhow!(
+                "Only closed terms compile, '{}' is neither bound nor defined",
+                name
+            ));
+        }
+        // The entry is known once the definition is compiled
+        self.globals.push((name.to_string(), 0));
+        self.indices
+            .insert(name.to_string(), self.globals.len() - 1);
+        Ok(self.globals.len() - 1)
+    }
+}
+
+#[derive(Clone)]
+struct Closure {
+    pc: usize,
+    env: Env,
+}
+
+type Env = Option<Rc<Binding>>;
+
+struct Binding {
+    closure: Closure,
+    next: Env,
+}
+
+fn lookup(env: &Env, index: usize) -> &Closure {
+    let mut binding = env.as_ref();
+    for _ in 0..index {
+        binding = binding.and_then(|binding| binding.next.as_ref());
+    }
+    &binding.expect("compiled terms are closed").closure
+}
+
+impl Machine<'_> {
+    // The interpreter loop, from the code of the term at address 0
+    pub(crate) fn run_program(mut self, program: &Program) -> MachineRun {
+        let code = &program.code;
+        let mut pc = 0;
+        let mut env: Env = None;
+        let mut stack = Vec::new();
+        let mut acc = Closure { pc: 0, env: None };
+        let stop = loop {
+            match &code[pc] {
+                Instr::Access(index) => {
+                    acc = lookup(&env, *index).clone();
+                    pc += 1;
+                }
+                Instr::Global(global) => {
+                    if let Err(stop) = self.tick() {
+                        break Some(stop);
+                    }
+                    self.delta += 1;
+                    acc = Closure {
+                        pc: program.globals[*global].1,
+                        env: None,
+                    };
+                    pc += 1;
+                }
+                Instr::Closure(arg) => {
+                    acc = Closure {
+                        pc: *arg,
+                        env: env.clone(),
+                    };
+                    pc += 1;
+                }
+                Instr::Push => {
+                    stack.push(acc.clone());
+                    pc += 1;
+                }
+                Instr::Apply => {
+                    pc = acc.pc;
+                    env = acc.env.clone();
+                }
+                Instr::Grab(_) => {
+                    let Some(arg) = stack.pop() else {
+                        break None;
+                    };
+                    if let Err(stop) = self.tick() {
+                        break Some(stop);
+                    }
+                    self.beta += 1;
+                    env = Some(Rc::new(Binding {
+                        closure: arg,
+                        next: env,
+                    }));
+                    pc += 1;
+                }
+            }
+        };
+
+        let termination = match stop {
+            None => Termination::NormalForm,
+            Some(Stop::Cancelled) => Termination::Cancelled,
+            Some(Stop::Limit | Stop::Free(_)) => Termination::StepLimit,
+        };
+        MachineRun {
+            result: stop
+                .is_none()
+                .then(|| program.decompile(pc, &env, 0).to_expr()),
+            beta: self.beta,
+            delta: self.delta,
+            termination,
+            trace: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instr::Access(index) => write!(f, "ACCESS {}", index),
+            Instr::Global(global) => write!(f, "GLOBAL {}", global),
+            Instr::Closure(arg) => write!(f, "CLOSURE {}", arg),
+            Instr::Push => f.write_str("PUSH"),
+            Instr::Apply => f.write_str("APPLY"),
+            Instr::Grab(param) => write!(f, "GRAB {}", param),
+        }
+    }
+}
+
+// One instruction per line after its address, each definition under its name
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.code.len().saturating_sub(1).to_string().len();
+        writeln!(f, "{}", self.term)?;
+        for (pc, instr) in self.code.iter().enumerate() {
+            if let Some((name, _)) = self.globals.iter().find(|(_, entry)| *entry == pc) {
+                writeln!(f, "{}:", name)?;
+            }
+            match instr {
+                Instr::Global(global) => writeln!(
+                    f,
+                    "  {:>width$}  {:<12} ; {}",
+                    pc,
+                    instr.to_string(),
+                    self.globals[*global].0
+                )?,
+                _ => writeln!(f, "  {:>width$}  {}", pc, instr)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use super::machine::{bind, Closure, Frame, Machine, MachineState, Stop, Transition};
+use crate::ast::Expr;
+
+impl Machine<'_> {
+    // Call by value: the function of an application is evaluated first, then its
+    // argument, and only then is the argument bound. Abstractions are the values, the
+    // stack is the continuation
+    pub(super) fn cek(&mut self, state: &mut MachineState) -> Result<Option<Transition>, Stop> {
+        let term = state.term.clone();
+        match &*term {
+            Expr::Apl(func, arg) => {
+                state.stack.push(Frame::Arg(Closure {
+                    term: arg.clone(),
+                    env: state.env.clone(),
+                }));
+                state.term = func.clone();
+                Ok(Some(Transition::Push))
+            }
+            Expr::Abs(..) => {
+                let value = Closure {
+                    term: term.clone(),
+                    env: state.env.clone(),
+                };
+                match state.stack.pop() {
+                    None => Ok(None),
+                    Some(Frame::Arg(arg)) => {
+                        state.stack.push(Frame::Fun(value));
+                        state.term = arg.term;
+                        state.env = arg.env;
+                        Ok(Some(Transition::Argument))
+                    }
+                    Some(Frame::Fun(func)) => {
+                        let Expr::Abs(param, body) = &*func.term else {
+                            unreachable!("only abstractions are passed arguments")
+                        };
+                        self.tick()?;
+                        self.beta += 1;
+                        state.env = bind(&func.env, param, value);
+                        state.term = body.clone();
+                        Ok(Some(Transition::Apply(param.clone())))
+                    }
+                }
+            }
+            Expr::Var(name) => self.access(state, name).map(Some),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use super::Step;
+use crate::ast::{Expr, RedType};
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+/// Why an evaluation stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    NormalForm,
+    // The step limit was reached first
+    StepLimit,
+    // A term grew past the size limit
+    SizeLimit,
+    // A term grew past the depth limit
+    DepthLimit,
+    // A hook vetoed a step or asked to stop
+    Stopped,
+    // The cancel flag was set
+    Cancelled,
+}
+
+/// Counts gathered over an evaluation. There is no η rule, so no η steps either.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub beta: usize,
+    pub alpha: usize,
+    /// Definitions unfolded.
+    pub delta: usize,
+    /// Steps taken inside an application, also counted by their kind.
+    pub contextual: usize,
+    /// Variable occurrences replaced by β-reductions.
+    pub substitutions: usize,
+    /// Largest term seen, in nodes.
+    pub max_size: usize,
+    pub max_depth: usize,
+    /// Time spent reducing.
+    pub time: Duration,
+    /// Unfoldings of each definition.
+    pub unfolded: BTreeMap<String, usize>,
+}
+
+impl Stats {
+    pub(crate) fn new(expr: &Expr) -> Self {
+        Stats {
+            max_size: expr.size(),
+            max_depth: expr.depth(),
+            ..Stats::default()
+        }
+    }
+
+    pub fn steps(&self) -> usize {
+        self.beta + self.alpha + self.delta
+    }
+
+    // `red_type` as `Expr::reduce_in` reports it, `redex` is the subterm it reduced
+    pub(crate) fn record(
+        &mut self,
+        red_type: &RedType,
+        contextual: bool,
+        redex: &Expr,
+        result: &Expr,
+        time: Duration,
+    ) {
+        match red_type {
+            RedType::BetaReduction(param) => {
+                self.beta += 1;
+                if let Expr::Apl(func, _) = redex
+                    && let Expr::Abs(_, body) = &**func
+                {
+                    self.substitutions += body.occurrences(param);
+                }
+            }
+            RedType::AlphaConversion(..) | RedType::Simplification(_) => self.alpha += 1,
+            RedType::DeltaReduction(name) => {
+                self.delta += 1;
+                *self.unfolded.entry(name.clone()).or_default() += 1;
+            }
+            RedType::ContextualReduction(_) | RedType::NoReduction => {}
+        }
+        self.contextual += contextual as usize;
+        self.max_size = self.max_size.max(result.size());
+        self.max_depth = self.max_depth.max(result.depth());
+        self.time += time;
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} steps: {} beta, {} alpha, {} delta, {} contextual",
+            self.steps(),
+            self.beta,
+            self.alpha,
+            self.delta,
+            self.contextual
+        )?;
+        writeln!(f, "{} substitutions", self.substitutions)?;
+        writeln!(
+            f,
+            "max size {}, max depth {}",
+            self.max_size, self.max_depth
+        )?;
+        write!(f, "took {:.2?}", self.time)?;
+        if !self.unfolded.is_empty() {
+            let unfolded = self
+                .unfolded
+                .iter()
+                .map(|(name, count)| format!("{} {}", name, count))
+                .collect::<Vec<_>>();
+            write!(f, "\nunfolded {}", unfolded.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of `Vm::evaluate`.
+#[derive(Debug, Clone)]
+pub struct Evaluation {
+    pub input: Expr,
+    /// The last term reached, the normal form when `termination` is `NormalForm`.
+    pub result: Expr,
+    pub steps: Vec<Step>,
+    pub stats: Stats,
+    pub termination: Termination,
+}
+
+impl Evaluation {
+    pub fn is_normal_form(&self) -> bool {
+        self.termination == Termination::NormalForm
+    }
+}
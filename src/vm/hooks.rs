@@ -0,0 +1,24 @@
+use super::Step;
+use crate::ast::{Expr, Path, RedType};
+
+/// What a hook wants the vm to do next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    /// Before a step it is not taken, after a step no further ones are.
+    Stop,
+}
+
+/// A reduction about to be taken, as seen by `Vm::before_step` hooks.
+#[derive(Debug)]
+pub struct Reduction<'a> {
+    /// The whole term being reduced.
+    pub term: &'a Expr,
+    /// The subterm the step contracts, unfolds or renames.
+    pub redex: &'a Expr,
+    pub path: &'a Path,
+    pub red_type: &'a RedType,
+}
+
+pub(crate) type BeforeHook = Box<dyn FnMut(&Reduction<'_>) -> Control + Send>;
+pub(crate) type AfterHook = Box<dyn FnMut(&Step) -> Control + Send>;
@@ -0,0 +1,31 @@
+use super::machine::{bind, Closure, Frame, Machine, MachineState, Stop, Transition};
+use crate::ast::Expr;
+
+impl Machine<'_> {
+    // Call by name to weak head normal form: arguments are pushed unevaluated and only
+    // looked at once their variable reaches the head
+    pub(super) fn krivine(&mut self, state: &mut MachineState) -> Result<Option<Transition>, Stop> {
+        let term = state.term.clone();
+        match &*term {
+            Expr::Apl(func, arg) => {
+                state.stack.push(Frame::Arg(Closure {
+                    term: arg.clone(),
+                    env: state.env.clone(),
+                }));
+                state.term = func.clone();
+                Ok(Some(Transition::Push))
+            }
+            Expr::Abs(param, body) => {
+                let Some(Frame::Arg(arg)) = state.stack.pop() else {
+                    return Ok(None);
+                };
+                self.tick()?;
+                self.beta += 1;
+                state.env = bind(&state.env, param, arg);
+                state.term = body.clone();
+                Ok(Some(Transition::Grab(param.clone())))
+            }
+            Expr::Var(name) => self.access(state, name).map(Some),
+        }
+    }
+}
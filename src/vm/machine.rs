@@ -0,0 +1,345 @@
+use super::Termination;
+use crate::ast::{Definitions, Expr, Term};
+use anyhow::{anyhow, Result};
+use std::{
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Outcome of `Vm::evaluate_krivine`, `Vm::evaluate_cek` and `Vm::run_program`.
+#[derive(Debug, Clone)]
+pub struct MachineRun {
+    /// The value, its closure read back as a term. `None` when the machine did not get
+    /// there.
+    pub result: Option<Expr>,
+    pub beta: usize,
+    pub delta: usize,
+    pub termination: Termination,
+    /// Every state from the initial one, empty unless asked for.
+    pub trace: Vec<MachineState>,
+}
+
+impl MachineRun {
+    pub fn steps(&self) -> usize {
+        self.beta + self.delta
+    }
+}
+
+/// A state of an abstract machine: the term under evaluation, the environment its
+/// variables are bound in, and the stack of what to do with its value.
+#[derive(Debug, Clone)]
+pub struct MachineState {
+    /// How the machine got here, `None` for the initial state.
+    pub transition: Option<Transition>,
+    pub(super) term: Term,
+    pub(super) env: Env,
+    pub(super) stack: Vec<Frame>,
+}
+
+/// A move of an abstract machine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transition {
+    // The argument of an application goes on the stack, the function is evaluated first
+    Push,
+    // Krivine: an abstraction binds the argument on top of the stack, a β step
+    Grab(String),
+    // CEK: the function is a value, the argument is evaluated next
+    Argument,
+    // CEK: the function on the stack binds the value of its argument, a β step
+    Apply(String),
+    // A variable continues with the closure it is bound to
+    Access(String),
+    // A free variable continues with its definition, a δ step
+    Unfold(String),
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Closure {
+    pub(super) term: Term,
+    pub(super) env: Env,
+}
+
+pub(super) type Env = Option<Rc<Binding>>;
+
+#[derive(Debug)]
+pub(super) struct Binding {
+    name: String,
+    closure: Closure,
+    next: Env,
+}
+
+// What waits for the value of the term under evaluation
+#[derive(Debug, Clone)]
+pub(super) enum Frame {
+    // To be applied to this argument
+    Arg(Closure),
+    // To be passed to this function
+    Fun(Closure),
+}
+
+// Why a machine stopped before a value
+pub(super) enum Stop {
+    Limit,
+    Cancelled,
+    // A variable neither bound nor defined reached the control
+    Free(String),
+}
+
+impl MachineState {
+    pub fn term(&self) -> &Expr {
+        &self.term
+    }
+
+    pub fn fmt_with_config(&self, color: bool, utf8: bool, merge: bool) -> String {
+        let gray = if color { "\x1b[0m\x1b[38;5;240m" } else { "" };
+        let reset = if color { "\x1b[0m" } else { "" };
+        let (open, close) = if utf8 { ("⟨", "⟩") } else { ("<", ">") };
+        let hole = Term::new(Expr::Var(if utf8 { "□" } else { "_" }.to_string()));
+        let expr = |expr: Expr| expr.fmt_with_config(color, utf8, merge);
+
+        let mut env = Vec::new();
+        let mut binding = self.env.as_ref();
+        while let Some(current) = binding {
+            env.push(format!(
+                "{}{} = {}",
+                current.name,
+                gray,
+                expr((*current.closure.term).clone())
+            ));
+            binding = current.next.as_ref();
+        }
+        // The frame on top first, each as the application its value goes into
+        let stack = self
+            .stack
+            .iter()
+            .rev()
+            .map(|frame| match frame {
+                Frame::Arg(arg) => expr(Expr::Apl(hole.clone(), arg.term.clone())),
+                Frame::Fun(func) => expr(Expr::Apl(func.term.clone(), hole.clone())),
+            })
+            .collect::<Vec<_>>();
+        let sep = format!("{}, {}", gray, reset);
+        format!(
+            "{gray}{open}{reset}{}{gray} | [{reset}{}{gray}] | [{reset}{}{gray}]{close}{reset}",
+            expr((*self.term).clone()),
+            env.join(&sep),
+            stack.join(&sep),
+        )
+    }
+}
+
+impl Transition {
+    pub fn fmt_with_config(&self, color: bool) -> String {
+        let reset = if color { "\x1b[0m" } else { "" };
+        let type_ = if color { "\x1b[1m\x1b[38;5;3m" } else { "" };
+        let gray = if color { "\x1b[0m\x1b[38;5;240m" } else { "" };
+        match self {
+            Transition::Push | Transition::Argument => {
+                format!("{}->{}{}{}", gray, type_, self.name(), reset)
+            }
+            Transition::Grab(name)
+            | Transition::Apply(name)
+            | Transition::Access(name)
+            | Transition::Unfold(name) => {
+                format!(
+                    "{}->{}{}{}({}{}{}){}",
+                    gray,
+                    type_,
+                    self.name(),
+                    gray,
+                    reset,
+                    name,
+                    gray,
+                    reset
+                )
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Transition::Push => "push",
+            Transition::Grab(_) => "grab",
+            Transition::Argument => "argument",
+            Transition::Apply(_) => "apply",
+            Transition::Access(_) => "access",
+            Transition::Unfold(_) => "unfold",
+        }
+    }
+}
+
+// Moves a machine to its next state, `None` once the state holds a value
+pub(super) type Step<'a> =
+    fn(&mut Machine<'a>, &mut MachineState) -> Result<Option<Transition>, Stop>;
+
+// Runs an abstract machine on a closed term, with closures instead of substitution.
+// The machines differ in their `Step`
+pub(crate) struct Machine<'a> {
+    pub(super) definitions: &'a Definitions,
+    max_steps: Option<usize>,
+    cancel: Option<Arc<AtomicBool>>,
+    pub(super) beta: usize,
+    pub(super) delta: usize,
+}
+
+impl<'a> Machine<'a> {
+    pub(crate) fn new(
+        definitions: &'a Definitions,
+        max_steps: Option<usize>,
+        cancel: Option<Arc<AtomicBool>>,
+    ) -> Self {
+        Machine {
+            definitions,
+            max_steps,
+            cancel,
+            beta: 0,
+            delta: 0,
+        }
+    }
+
+    // Hands every state from the initial one to `observe`, the run has no trace
+    pub(crate) fn run(
+        mut self,
+        expr: &Expr,
+        step: Step<'a>,
+        observe: &mut dyn FnMut(&MachineState),
+    ) -> Result<MachineRun> {
+        if let Some(name) = expr
+            .free_vars()
+            .into_iter()
+            .find(|name| !self.definitions.contains_key(name))
+        {
+            return Err(free(&name));
+        }
+
+        let mut state = MachineState {
+            transition: None,
+            term: Term::new(expr.clone()),
+            env: None,
+            stack: Vec::new(),
+        };
+        let termination = loop {
+            observe(&state);
+            match step(&mut self, &mut state) {
+                Ok(Some(transition)) => state.transition = Some(transition),
+                Ok(None) => break Termination::NormalForm,
+                Err(Stop::Limit) => break Termination::StepLimit,
+                Err(Stop::Cancelled) => break Termination::Cancelled,
+                Err(Stop::Free(name)) => return Err(free(&name)),
+            }
+        };
+
+        let result = (termination == Termination::NormalForm)
+            .then(|| self.read_back(&state.term, &state.env, &mut Vec::new()));
+        Ok(MachineRun {
+            result,
+            beta: self.beta,
+            delta: self.delta,
+            termination,
+            trace: Vec::new(),
+        })
+    }
+
+    // Counts a β or δ step, failing once over the limit or cancelled
+    pub(super) fn tick(&self) -> Result<(), Stop> {
+        if self
+            .max_steps
+            .is_some_and(|max| self.beta + self.delta >= max)
+        {
+            return Err(Stop::Limit);
+        }
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+        {
+            return Err(Stop::Cancelled);
+        }
+        Ok(())
+    }
+
+    // Continues a variable with its closure, or with its definition in an empty
+    // environment
+    pub(super) fn access(
+        &mut self,
+        state: &mut MachineState,
+        name: &str,
+    ) -> Result<Transition, Stop> {
+        if let Some(closure) = find(&state.env, name) {
+            state.term = closure.term.clone();
+            state.env = closure.env.clone();
+            return Ok(Transition::Access(name.to_string()));
+        }
+        let definition = self
+            .definitions
+            .get(name)
+            .ok_or_else(|| Stop::Free(name.to_string()))?;
+        self.tick()?;
+        self.delta += 1;
+        state.term = Term::new(definition.clone());
+        state.env = None;
+        Ok(Transition::Unfold(name.to_string()))
+    }
+
+    // Substitutes the closures of the environment back into the term. They are closed
+    // but for the definitions they use, so binders named like a definition are renamed
+    // apart from the enclosing ones in `scope`
+    fn read_back(&self, term: &Term, env: &Env, scope: &mut Vec<String>) -> Expr {
+        match &**term {
+            Expr::Var(name) => match find(env, name) {
+                Some(closure) => self.read_back(&closure.term, &closure.env, scope),
+                None => (**term).clone(),
+            },
+            Expr::Abs(param, body) => {
+                let mut name = param.clone();
+                let mut counter = 0;
+                while self.definitions.contains_key(&name) || scope.contains(&name) {
+                    counter += 1;
+                    name = format!("{}_{}", param, counter);
+                }
+                // The binder reads back as the bare variable
+                let var = Closure {
+                    term: Term::new(Expr::Var(name.clone())),
+                    env: None,
+                };
+                scope.push(name);
+                let body = self.read_back(body, &bind(env, param, var), scope);
+                let name = scope.pop().unwrap_or_default();
+                Expr::Abs(name, Term::new(body))
+            }
+            Expr::Apl(func, arg) => Expr::Apl(
+                Term::new(self.read_back(func, env, scope)),
+                Term::new(self.read_back(arg, env, scope)),
+            ),
+        }
+    }
+}
+
+fn free(name: &str) -> anyhow::Error {
+    anyhow!(
+        "Abstract machines evaluate closed terms, '{}' is neither bound nor defined",
+        name
+    )
+}
+
+pub(super) fn bind(env: &Env, name: &str, closure: Closure) -> Env {
+    Some(Rc::new(Binding {
+        name: name.to_string(),
+        closure,
+        next: env.clone(),
+    }))
+}
+
+fn find<'e>(env: &'e Env, name: &str) -> Option<&'e Closure> {
+    let mut env = env.as_ref();
+    while let Some(binding) = env {
+        if binding.name == name {
+            return Some(&binding.closure);
+        }
+        env = binding.next.as_ref();
+    }
+    None
+}
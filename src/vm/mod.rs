@@ -1,30 +1,171 @@
 use crate::{
-    ast::{Expr, RedType},
+    ast::{Definitions, Expr, Path, RedType, Strategy},
     lexer::{Lexer, Parser},
 };
 use anyhow::{anyhow, Result};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+mod backend;
+mod bytecode;
+mod cek;
+mod evaluation;
+mod hooks;
+mod krivine;
+mod machine;
+mod need;
+mod options;
+mod steps;
+
+pub use backend::Backend;
+pub use bytecode::{Instr, Program};
+pub use evaluation::{Evaluation, Stats, Termination};
+pub use hooks::{Control, Reduction};
+pub use machine::{MachineRun, MachineState, Transition};
+pub use need::ByNeed;
+pub use options::Options;
+pub use steps::Steps;
+
+/// One entry of a reduction trace.
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub red_type: RedType,
+    // The rule applied at the redex, `red_type` reports steps inside an application as
+    // contextual instead
+    pub kind: RedType,
+    pub expr: Expr,
+    // Position of the contracted redex in the previous term and of its contractum in
+    // `expr`, `None` for steps that rename the whole term
+    pub redex: Option<Path>,
+}
 
 pub struct Vm {
     current_expr: Option<Expr>,
+    definitions: Definitions,
     lexer: Lexer,
+    strategy: Strategy,
+    max_steps: Option<usize>,
+    max_size: Option<usize>,
+    max_depth: Option<usize>,
+    before_hooks: Vec<hooks::BeforeHook>,
+    after_hooks: Vec<hooks::AfterHook>,
+    // A hook asked to stop, the next step ends the iteration
+    stopped: bool,
+    cancel: Option<Arc<AtomicBool>>,
+    stats: Stats,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Vm {
     pub fn new() -> Self {
         Vm {
             current_expr: None,
+            definitions: Definitions::new(),
             lexer: Lexer::new(),
+            strategy: Strategy::default(),
+            max_steps: None,
+            max_size: None,
+            max_depth: None,
+            before_hooks: Vec::new(),
+            after_hooks: Vec::new(),
+            stopped: false,
+            cancel: None,
+            stats: Stats::default(),
         }
     }
 
-    pub fn parse_expr(&mut self, input: &str) -> Result<()> {
+    /// A vm with the same definitions, strategy, limits and cancel flag, but no hooks
+    /// and no expression, to evaluate on another thread.
+    pub fn fork(&self) -> Self {
+        Vm {
+            definitions: self.definitions.clone(),
+            strategy: self.strategy,
+            max_steps: self.max_steps,
+            max_size: self.max_size,
+            max_depth: self.max_depth,
+            cancel: self.cancel.clone(),
+            ..Vm::new()
+        }
+    }
+
+    /// Builds a `Vm` with the given strategy and limits, loading the prelude.
+    pub fn with_options(options: Options) -> Result<Self> {
+        let mut vm = Vm::new();
+        vm.set_strategy(options.strategy);
+        vm.set_max_steps(options.max_steps);
+        vm.set_max_size(options.max_size);
+        vm.set_max_depth(options.max_depth);
+        vm.load_prelude(&options.prelude)?;
+        Ok(vm)
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
+    pub fn set_strategy(&mut self, strategy: Strategy) {
+        self.strategy = strategy;
+    }
+
+    pub fn max_steps(&self) -> Option<usize> {
+        self.max_steps
+    }
+
+    /// Limits how many reductions a single `eval` may perform, `None` means no limit.
+    pub fn set_max_steps(&mut self, max_steps: Option<usize>) {
+        self.max_steps = max_steps;
+    }
+
+    pub fn max_size(&self) -> Option<usize> {
+        self.max_size
+    }
+
+    /// Stops evaluations once a term grows past `max_size` nodes, `None` means no limit.
+    pub fn set_max_size(&mut self, max_size: Option<usize>) {
+        self.max_size = max_size;
+    }
+
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Stops evaluations once a term grows deeper than `max_depth` levels, `None` means no
+    /// limit. Reducing and printing a term recurses as deep as it is.
+    ///
+    /// ```
+    /// use rambda::vm::{Termination, Vm};
+    ///
+    /// let mut vm = Vm::new();
+    /// vm.set_max_depth(Some(100));
+    /// let evaluation = vm.evaluate("(\\x. a (x x)) (\\x. a (x x))")?;
+    /// assert_eq!(evaluation.termination, Termination::DepthLimit);
+    /// assert!(evaluation.result.depth() > 100);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_max_depth(&mut self, max_depth: Option<usize>) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn parse(&mut self, input: &str) -> Result<Expr> {
         self.lexer.reload(input);
         let tokens = self.lexer.tokenize();
         let mut parser = Parser::new(tokens);
-        let parsed = parser
-            .parse()
-            .ok_or(anyhow!("Failed to parse expression"))?;
-        self.current_expr = Some(parsed);
+        parser.parse().ok_or(anyhow!("Failed to parse expression"))
+    }
+
+    pub fn parse_expr(&mut self, input: &str) -> Result<()> {
+        let parsed = self.parse(input)?;
+        self.set_expr(parsed);
 
         Ok(())
     }
@@ -33,31 +174,639 @@ impl Vm {
         self.current_expr.as_ref()
     }
 
-    pub fn eval(&mut self) -> Result<Vec<(RedType, Expr)>> {
-        let mut steps: Vec<(RedType, Expr)> = vec![];
+    pub fn set_expr(&mut self, expr: Expr) {
+        self.stats = Stats::new(&expr);
+        self.current_expr = Some(expr);
+        self.stopped = false;
+    }
+
+    /// Binds `name` to the parsed `input`, free occurrences of `name` unfold to it during
+    /// evaluation. Redefining a name replaces the previous definition.
+    pub fn define(&mut self, name: &str, input: &str) -> Result<()> {
+        let parsed = self.parse(input)?;
+        self.definitions.insert(name.to_string(), parsed);
 
-        let mut is_normal_form = false;
-        while !is_normal_form {
-            if let Some(expr) = &self.current_expr {
-                let (next_expr, reduction_type) = expr.eval_step();
-                if reduction_type != RedType::NoReduction {
-                    steps.push((reduction_type, next_expr.clone()));
-                }
-                self.current_expr = Some(next_expr);
-            } else {
-                return Err(anyhow!("No expression to evaluate"));
+        Ok(())
+    }
+
+    pub fn define_expr(&mut self, name: &str, expr: Expr) {
+        self.definitions.insert(name.to_string(), expr);
+    }
+
+    /// Defines every `name := body` line of `source`, skipping blank lines and `#` comments.
+    pub fn load_prelude(&mut self, source: &str) -> Result<()> {
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
             }
-            is_normal_form = self.current_expr.as_ref().unwrap().is_normal_form();
+            let (name, body) = line
+                .split_once(":=")
+                .ok_or(anyhow!("Prelude line {}: expected `name := body`", i + 1))?;
+            self.define(name.trim(), body)
+                .map_err(|err| anyhow!("Prelude line {}: {}", i + 1, err))?;
+        }
+        Ok(())
+    }
+
+    pub fn clear_definitions(&mut self) {
+        self.definitions.clear();
+    }
+
+    pub fn definitions(&self) -> &Definitions {
+        &self.definitions
+    }
+
+    pub fn is_normal_form(&self) -> bool {
+        self.current_expr
+            .as_ref()
+            .is_some_and(|expr| expr.is_normal_form_in(&self.definitions))
+    }
+
+    /// Calls `hook` before each reduction `step` takes, it may veto the reduction, which
+    /// ends the evaluation.
+    pub fn before_step(&mut self, hook: impl FnMut(&Reduction<'_>) -> Control + Send + 'static) {
+        self.before_hooks.push(Box::new(hook));
+    }
+
+    /// Calls `hook` with each step `step` took, it may stop the evaluation there.
+    ///
+    /// ```
+    /// use rambda::vm::{Control, Termination, Vm};
+    ///
+    /// let mut vm = Vm::new();
+    /// let mut taken = 0;
+    /// vm.after_step(move |_| {
+    ///     taken += 1;
+    ///     if taken == 2 { Control::Stop } else { Control::Continue }
+    /// });
+    /// let evaluation = vm.evaluate("(\\x.x x) (\\x.x x)")?;
+    /// assert_eq!(evaluation.termination, Termination::Stopped);
+    /// assert_eq!(evaluation.steps.len(), 2);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn after_step(&mut self, hook: impl FnMut(&Step) -> Control + Send + 'static) {
+        self.after_hooks.push(Box::new(hook));
+    }
+
+    /// Ends evaluations before their next step once `flag` is set, from any thread. The
+    /// flag is left set, its owner clears it.
+    pub fn set_cancel_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.cancel = Some(flag);
+    }
+
+    /// Whether the cancel flag is set. Callers stepping with `step` check it themselves,
+    /// `steps` ends on it.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    pub fn clear_hooks(&mut self) {
+        self.before_hooks.clear();
+        self.after_hooks.clear();
+    }
+
+    // Whether a hook stopped the evaluation since the last call
+    pub(crate) fn take_stopped(&mut self) -> bool {
+        std::mem::take(&mut self.stopped)
+    }
+
+    /// Performs a single reduction on the current expression, `None` once it is in normal form
+    /// or when a hook vetoed the reduction.
+    pub fn step(&mut self) -> Result<Option<Step>> {
+        let expr = self
+            .current_expr
+            .as_ref()
+            .ok_or(anyhow!("No expression to evaluate"))?;
+        if expr.is_normal_form_in(&self.definitions) {
+            return Ok(None);
+        }
+
+        let started = Instant::now();
+        let (next_expr, kind, path) = expr.reduce_in(self.strategy, &self.definitions);
+        let time = started.elapsed();
+        let red_type = kind.clone().in_context_of(&path);
+        let redex = expr.at_path(&path).unwrap_or(expr);
+        let reduction = Reduction {
+            term: expr,
+            redex,
+            path: &path,
+            red_type: &red_type,
+        };
+        if self
+            .before_hooks
+            .iter_mut()
+            .any(|hook| hook(&reduction) == Control::Stop)
+        {
+            self.stopped = true;
+            return Ok(None);
+        }
+
+        let contextual = matches!(red_type, RedType::ContextualReduction(_));
+        self.stats
+            .record(&kind, contextual, redex, &next_expr, time);
+        self.current_expr = Some(next_expr.clone());
+        let step = Step {
+            red_type,
+            kind,
+            expr: next_expr,
+            redex: Some(path),
+        };
+        for hook in &mut self.after_hooks {
+            if hook(&step) == Control::Stop {
+                self.stopped = true;
+            }
+        }
+        Ok(Some(step))
+    }
+
+    /// Statistics of the reductions since the current expression was set.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Iterates the reductions of the current expression lazily, ending at a normal form
+    /// or at one of the limits. Each step also becomes the current expression.
+    pub fn steps(&mut self) -> Steps<'_> {
+        Steps::new(self, 0)
+    }
+
+    /// Continues an iteration that already took `taken` steps, the step limit counts them.
+    pub fn steps_after(&mut self, taken: usize) -> Steps<'_> {
+        Steps::new(self, taken)
+    }
+
+    /// Parses and evaluates `input`, see `evaluate_expr`.
+    pub fn evaluate(&mut self, input: &str) -> Result<Evaluation> {
+        let expr = self.parse(input)?;
+        Ok(self.evaluate_expr(expr))
+    }
+
+    /// Reduces `expr` with the definitions, strategy and limits of the vm. A normal form
+    /// has its numbered variables renamed back, as `eval` does.
+    pub fn evaluate_expr(&mut self, expr: Expr) -> Evaluation {
+        self.set_expr(expr.clone());
+
+        let mut iter = self.steps();
+        let mut steps = iter.by_ref().collect::<Vec<_>>();
+        let termination = iter.termination().unwrap_or(Termination::NormalForm);
+        if termination == Termination::NormalForm
+            && let Ok(renamed) = self.simplify()
+        {
+            steps.extend(renamed);
         }
 
-        let (simplified, reductions) = self.current_expr.as_ref().unwrap().simplify_numbered_vars();
-        for reduction in reductions {
-            if let RedType::AlphaConversion(old, new) = reduction {
-                steps.push((RedType::AlphaConversion(old, new), simplified.clone()));
+        Evaluation {
+            input: expr,
+            result: self.current_expr.clone().unwrap(),
+            steps,
+            stats: self.stats.clone(),
+            termination,
+        }
+    }
+
+    /// Reduces `expr` to normal form by need, as a graph of thunks each evaluated at most
+    /// once, with the definitions, limits and cancel flag of the vm. Fails when a
+    /// definition needs its own value, which normal order would unfold forever.
+    ///
+    /// It reaches the normal form normal order does, in no more β and δ steps:
+    ///
+    /// ```
+    /// use rambda::vm::Vm;
+    ///
+    /// let mut vm = Vm::new();
+    /// vm.define("two", "\\f.\\x.f (f x)")?;
+    /// vm.define("mult", "\\m.\\n.\\f.m (n f)")?;
+    /// vm.define("k", "\\x.\\y.x")?;
+    /// let inputs = [
+    ///     "mult two two",
+    ///     "(\\x.x x) (two two)",
+    ///     "k (\\z.z) ((\\x.x x) (\\x.x x))",
+    ///     "\\y.(\\x.\\y.x y) y",
+    ///     "two (\\x.x free) y",
+    /// ];
+    /// for input in inputs {
+    ///     let expr = vm.parse(input)?;
+    ///     let by_need = vm.evaluate_by_need(&expr)?;
+    ///
+    ///     let evaluation = vm.evaluate_expr(expr);
+    ///     assert!(by_need.saved(&evaluation.stats) >= 0);
+    ///     assert!(by_need.result.unwrap().alpha_eq(&evaluation.result));
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn evaluate_by_need(&self, expr: &Expr) -> Result<ByNeed> {
+        need::Machine::new(
+            &self.definitions,
+            self.max_steps,
+            self.max_depth,
+            self.cancel.clone(),
+        )
+        .run(expr)
+    }
+
+    /// Evaluates the closed term `expr` to weak head normal form on a Krivine machine,
+    /// which binds arguments in environments instead of substituting them. Definitions
+    /// count as closed, the step limit counts β and δ steps. With `trace` every machine
+    /// state is kept.
+    ///
+    /// Normal order contracts the head redex first, so substitution reaches the same
+    /// weak head normal form in as many β steps:
+    ///
+    /// ```
+    /// use rambda::{ast::{Expr, RedType, Strategy}, vm::Vm};
+    ///
+    /// let mut vm = Vm::new();
+    /// vm.define("two", "\\f.\\x.f (f x)")?;
+    /// vm.define("k", "\\x.\\y.x")?;
+    /// for input in ["k (\\z.z) two", "two two", "(\\x.x x) (\\y.\\x.y x)", "k k k"] {
+    ///     let expr = vm.parse(input)?;
+    ///     let krivine = vm.evaluate_krivine(&expr, false)?;
+    ///
+    ///     let (mut whnf, mut beta) = (expr, 0);
+    ///     while !matches!(whnf, Expr::Abs(..)) {
+    ///         let (next, red, _) = whnf.reduce_in(Strategy::NormalOrder, vm.definitions());
+    ///         beta += matches!(red, RedType::BetaReduction(_)) as usize;
+    ///         whnf = next;
+    ///     }
+    ///     assert!(krivine.result.unwrap().alpha_eq(&whnf));
+    ///     assert_eq!(krivine.beta, beta);
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn evaluate_krivine(&self, expr: &Expr, trace: bool) -> Result<MachineRun> {
+        let mut states = Vec::new();
+        let mut run = self.evaluate_krivine_with(expr, |state| {
+            if trace {
+                states.push(state.clone());
+            }
+        })?;
+        run.trace = states;
+        Ok(run)
+    }
+
+    /// Like `evaluate_krivine`, handing each machine state to `observe` as it is reached
+    /// instead of keeping them all.
+    pub fn evaluate_krivine_with(
+        &self,
+        expr: &Expr,
+        mut observe: impl FnMut(&MachineState),
+    ) -> Result<MachineRun> {
+        self.machine()
+            .run(expr, machine::Machine::krivine, &mut observe)
+    }
+
+    /// Evaluates the closed term `expr` by value on a CEK machine: its control, the term
+    /// under evaluation, an environment of closures, and a continuation of what is left
+    /// to do. Stops at an abstraction, definitions and limits as in `evaluate_krivine`.
+    ///
+    /// A value has the same normal form as the term it came from:
+    ///
+    /// ```
+    /// use rambda::vm::Vm;
+    ///
+    /// let mut vm = Vm::new();
+    /// vm.define("two", "\\f.\\x.f (f x)")?;
+    /// vm.define("plus", "\\m.\\n.\\f.\\x.m f (n f x)")?;
+    /// for input in ["plus two two", "two two", "(\\x.\\y.y x) (two (\\z.z))"] {
+    ///     let expr = vm.parse(input)?;
+    ///     let value = vm.evaluate_cek(&expr, false)?.result.unwrap();
+    ///
+    ///     let expected = vm.evaluate_expr(expr).result;
+    ///     assert!(vm.evaluate_expr(value).result.alpha_eq(&expected));
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn evaluate_cek(&self, expr: &Expr, trace: bool) -> Result<MachineRun> {
+        let mut states = Vec::new();
+        let mut run = self.evaluate_cek_with(expr, |state| {
+            if trace {
+                states.push(state.clone());
             }
+        })?;
+        run.trace = states;
+        Ok(run)
+    }
+
+    /// Like `evaluate_cek`, handing each machine state to `observe` as it is reached.
+    pub fn evaluate_cek_with(
+        &self,
+        expr: &Expr,
+        mut observe: impl FnMut(&MachineState),
+    ) -> Result<MachineRun> {
+        self.machine()
+            .run(expr, machine::Machine::cek, &mut observe)
+    }
+
+    /// Compiles the closed term `expr` and the definitions it uses to bytecode for
+    /// `run_program`.
+    pub fn compile(&self, expr: &Expr) -> Result<Program> {
+        Program::compile(expr, &self.definitions)
+    }
+
+    /// Runs compiled code to weak head normal form, with the step limit and cancel flag
+    /// of the vm. It has no trace. The definitions are the ones compiled in.
+    pub fn run_program(&self, program: &Program) -> MachineRun {
+        self.machine().run_program(program)
+    }
+
+    /// Compiles and runs `expr`. The code evaluates as the Krivine machine does:
+    ///
+    /// ```
+    /// use rambda::vm::Vm;
+    ///
+    /// let mut vm = Vm::new();
+    /// vm.define("two", "\\f.\\x.f (f x)")?;
+    /// vm.define("k", "\\x.\\y.x")?;
+    /// for input in ["k (\\z.z) two", "two two", "(\\x.x x) (\\y.\\x.y x)", "two k k"] {
+    ///     let expr = vm.parse(input)?;
+    ///     let run = vm.evaluate_bytecode(&expr)?;
+    ///     let krivine = vm.evaluate_krivine(&expr, false)?;
+    ///
+    ///     assert!(run.result.unwrap().alpha_eq(&krivine.result.unwrap()));
+    ///     assert_eq!((run.beta, run.delta), (krivine.beta, krivine.delta));
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn evaluate_bytecode(&self, expr: &Expr) -> Result<MachineRun> {
+        Ok(self.run_program(&self.compile(expr)?))
+    }
+
+    fn machine(&self) -> machine::Machine<'_> {
+        machine::Machine::new(&self.definitions, self.max_steps, self.cancel.clone())
+    }
+
+    /// Reduces the current expression until it reaches a normal form or the step limit
+    /// is hit, in which case the current expression is left unreduced. The statistics of
+    /// the reduction are in `stats` afterwards.
+    pub fn eval(&mut self) -> Result<Vec<Step>> {
+        if self.current_expr.is_none() {
+            return Err(anyhow!("No expression to evaluate"));
+        }
+        let mut iter = self.steps();
+        let mut steps = iter.by_ref().collect::<Vec<_>>();
+        if iter.termination() == Some(Termination::NormalForm) {
+            steps.extend(self.simplify()?);
         }
+        Ok(steps)
+    }
 
+    /// Gives the numbered variables of fresh binders in the current expression their
+    /// names back, one alpha step per renamed binder. Meant for normal forms, fails
+    /// without a current expression.
+    pub fn simplify(&mut self) -> Result<Vec<Step>> {
+        let (simplified, reductions) = self
+            .current_expr
+            .as_ref()
+            .ok_or(anyhow!("No expression to simplify"))?
+            .simplify_numbered_vars();
+        let steps: Vec<Step> = reductions
+            .into_iter()
+            .filter_map(|reduction| match reduction {
+                RedType::AlphaConversion(old, new) => Some(Step {
+                    red_type: RedType::AlphaConversion(old.clone(), new.clone()),
+                    kind: RedType::AlphaConversion(old, new),
+                    expr: simplified.clone(),
+                    redex: None,
+                }),
+                _ => None,
+            })
+            .collect();
+        for step in &steps {
+            self.stats.record(
+                &step.red_type,
+                false,
+                &step.expr,
+                &step.expr,
+                Duration::ZERO,
+            );
+        }
         self.current_expr = Some(simplified);
         Ok(steps)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Closed with the definitions of `vm`, the last ones have no weak head normal form
+    const TERMS: &[&str] = &[
+        "id",
+        "id id id",
+        "k id two",
+        "k k k",
+        "two two",
+        "two k k",
+        "plus two two",
+        "mult two two",
+        "(\\x.x x) (\\y.\\x.y x)",
+        "fst (pair id omega)",
+        "k id omega",
+        "pair omega id",
+        "omega",
+        "(\\x.x x x) (\\x.x x x)",
+    ];
+
+    // Low enough for the terms that grow to stay shallow on a test thread
+    const MAX_STEPS: usize = 200;
+
+    fn vm() -> Vm {
+        let mut vm = Vm::new();
+        vm.load_prelude(
+            "id := \\x.x
+            k := \\x.\\y.x
+            two := \\f.\\x.f (f x)
+            plus := \\m.\\n.\\f.\\x.m f (n f x)
+            mult := \\m.\\n.\\f.m (n f)
+            pair := \\a.\\b.\\f.f a b
+            fst := \\p.p k
+            omega := (\\x.x x) (\\x.x x)",
+        )
+        .unwrap();
+        vm.set_max_steps(Some(MAX_STEPS));
+        vm
+    }
+
+    // The weak head normal form normal order reaches by substitution with its β and δ
+    // steps, `None` when it takes more than `MAX_STEPS`
+    fn whnf(vm: &Vm, expr: &Expr) -> Option<(Expr, usize, usize)> {
+        let (mut expr, mut beta, mut delta) = (expr.clone(), 0, 0);
+        while !matches!(expr, Expr::Abs(..)) {
+            if beta + delta >= MAX_STEPS {
+                return None;
+            }
+            let (next, red, _) = expr.reduce_in(Strategy::NormalOrder, vm.definitions());
+            match red {
+                RedType::BetaReduction(_) => beta += 1,
+                RedType::DeltaReduction(_) => delta += 1,
+                _ => {}
+            }
+            expr = next;
+        }
+        Some((expr, beta, delta))
+    }
+
+    fn parse(vm: &mut Vm, input: &str) -> Expr {
+        vm.parse(input).unwrap()
+    }
+
+    #[test]
+    fn krivine_reaches_the_weak_head_normal_form_of_substitution() {
+        let mut vm = vm();
+        for input in TERMS {
+            let expr = parse(&mut vm, input);
+            let krivine = vm.evaluate_krivine(&expr, false).unwrap();
+            match whnf(&vm, &expr) {
+                Some((whnf, beta, delta)) => {
+                    assert_eq!(krivine.termination, Termination::NormalForm, "{}", input);
+                    assert!(krivine.result.unwrap().alpha_eq(&whnf), "{}", input);
+                    assert_eq!((krivine.beta, krivine.delta), (beta, delta), "{}", input);
+                }
+                None => {
+                    assert_eq!(krivine.termination, Termination::StepLimit, "{}", input);
+                    assert!(krivine.result.is_none(), "{}", input);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn krivine_stops_at_the_step_limit_of_substitution() {
+        let mut vm = vm();
+        for input in TERMS {
+            let expr = parse(&mut vm, input);
+            let Some((_, beta, delta)) = whnf(&vm, &expr) else {
+                continue;
+            };
+            for max in 0..=beta + delta + 1 {
+                vm.set_max_steps(Some(max));
+                let krivine = vm.evaluate_krivine(&expr, false).unwrap();
+                let expected = match max >= beta + delta {
+                    true => Termination::NormalForm,
+                    false => Termination::StepLimit,
+                };
+                assert_eq!(krivine.termination, expected, "{} in {}", input, max);
+                assert!(krivine.steps() <= max, "{} in {}", input, max);
+            }
+            vm.set_max_steps(Some(MAX_STEPS));
+        }
+    }
+
+    #[test]
+    fn bytecode_runs_as_krivine_does() {
+        let mut vm = vm();
+        for input in TERMS {
+            let expr = parse(&mut vm, input);
+            let steps = whnf(&vm, &expr).map_or(MAX_STEPS, |(_, beta, delta)| beta + delta);
+            for max in [0, steps / 2, steps, MAX_STEPS] {
+                vm.set_max_steps(Some(max));
+                let run = vm.evaluate_bytecode(&expr).unwrap();
+                let krivine = vm.evaluate_krivine(&expr, false).unwrap();
+                assert_eq!(run.termination, krivine.termination, "{} in {}", input, max);
+                assert_eq!((run.beta, run.delta), (krivine.beta, krivine.delta));
+                match (run.result, krivine.result) {
+                    (Some(run), Some(krivine)) => assert!(run.alpha_eq(&krivine), "{}", input),
+                    (run, krivine) => assert_eq!(run.is_none(), krivine.is_none(), "{}", input),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cek_values_have_the_normal_form_of_their_term() {
+        let mut vm = vm();
+        for input in TERMS {
+            let expr = parse(&mut vm, input);
+            let cek = vm.evaluate_cek(&expr, false).unwrap();
+            let evaluation = vm.evaluate_expr(expr);
+            if cek.termination == Termination::NormalForm {
+                let value = vm.evaluate_expr(cek.result.unwrap());
+                assert!(evaluation.is_normal_form(), "{}", input);
+                assert!(value.result.alpha_eq(&evaluation.result), "{}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn cek_diverges_on_arguments_normal_order_drops() {
+        let mut vm = vm();
+        for input in ["k id omega", "fst (pair id omega)", "omega"] {
+            let expr = parse(&mut vm, input);
+            let cek = vm.evaluate_cek(&expr, false).unwrap();
+            assert_eq!(cek.termination, Termination::StepLimit, "{}", input);
+            assert_eq!(cek.steps(), MAX_STEPS, "{}", input);
+        }
+    }
+
+    #[test]
+    fn cek_stops_at_the_step_limit() {
+        let mut vm = vm();
+        for input in TERMS {
+            let expr = parse(&mut vm, input);
+            let cek = vm.evaluate_cek(&expr, false).unwrap();
+            if cek.termination != Termination::NormalForm {
+                continue;
+            }
+            let steps = cek.steps();
+            for max in 0..=steps + 1 {
+                vm.set_max_steps(Some(max));
+                let cek = vm.evaluate_cek(&expr, false).unwrap();
+                let expected = match max >= steps {
+                    true => Termination::NormalForm,
+                    false => Termination::StepLimit,
+                };
+                assert_eq!(cek.termination, expected, "{} in {}", input, max);
+            }
+            vm.set_max_steps(Some(MAX_STEPS));
+        }
+    }
+
+    #[test]
+    fn by_need_reaches_the_normal_form_of_normal_order_in_fewer_steps() {
+        let mut vm = vm();
+        for input in TERMS
+            .iter()
+            .chain(&["two (\\x.x id) k", "\\y.(\\x.\\y.x y) y"])
+        {
+            let expr = parse(&mut vm, input);
+            let by_need = vm.evaluate_by_need(&expr).unwrap();
+            let evaluation = vm.evaluate_expr(expr);
+            match evaluation.is_normal_form() {
+                true => {
+                    assert_eq!(by_need.termination, Termination::NormalForm, "{}", input);
+                    assert!(by_need.saved(&evaluation.stats) >= 0, "{}", input);
+                    assert!(by_need.result.unwrap().alpha_eq(&evaluation.result));
+                }
+                false => {
+                    assert_eq!(by_need.termination, Termination::StepLimit, "{}", input);
+                    assert!(by_need.result.is_none(), "{}", input);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn by_need_stops_at_the_step_limit() {
+        let mut vm = vm();
+        for input in TERMS.iter().chain(&["two (\\x.x id) k"]) {
+            let expr = parse(&mut vm, input);
+            let by_need = vm.evaluate_by_need(&expr).unwrap();
+            if by_need.termination != Termination::NormalForm {
+                continue;
+            }
+            for max in 0..=by_need.steps() + 1 {
+                vm.set_max_steps(Some(max));
+                let limited = vm.evaluate_by_need(&expr).unwrap();
+                let expected = match max >= by_need.steps() {
+                    true => Termination::NormalForm,
+                    false => Termination::StepLimit,
+                };
+                assert_eq!(limited.termination, expected, "{} in {}", input, max);
+            }
+            vm.set_max_steps(Some(MAX_STEPS));
+        }
+    }
+}
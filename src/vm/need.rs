@@ -0,0 +1,392 @@
+use super::{Stats, Termination};
+use crate::ast::{Definitions, Expr, Term};
+use anyhow::{anyhow, Result};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Outcome of `Vm::evaluate_by_need`.
+#[derive(Debug, Clone)]
+pub struct ByNeed {
+    /// The normal form, `None` when the reduction did not finish.
+    pub result: Option<Expr>,
+    pub beta: usize,
+    /// Definitions unfolded, each at most once.
+    pub delta: usize,
+    pub termination: Termination,
+}
+
+impl ByNeed {
+    pub fn steps(&self) -> usize {
+        self.beta + self.delta
+    }
+
+    /// β and δ steps sharing saved over `naive`, the statistics of a normal order
+    /// reduction of the same term. Negative when sharing took more.
+    pub fn saved(&self, naive: &Stats) -> isize {
+        (naive.beta + naive.delta) as isize - self.steps() as isize
+    }
+}
+
+// A graph of thunks, each updated in place with its weak head normal form the first time
+// it is needed, so an argument used twice is only reduced once
+pub(crate) struct Machine<'a> {
+    heap: Vec<Cell>,
+    definitions: &'a Definitions,
+    // Thunks of the definitions unfolded so far
+    unfolded: HashMap<String, usize>,
+    // Free names of the input and the definitions, binders read back avoid them
+    globals: HashSet<String>,
+    max_steps: Option<usize>,
+    // Deepest normal form read back, a cyclic value reads back forever
+    max_depth: Option<usize>,
+    cancel: Option<Arc<AtomicBool>>,
+    beta: usize,
+    delta: usize,
+}
+
+enum Cell {
+    Thunk(Term, Env),
+    // Being evaluated, needing it again means it has no weak head normal form
+    Busy,
+    Value(Value),
+}
+
+#[derive(Clone)]
+enum Value {
+    Closure(Rc<Closure>),
+    // A variable applied to thunks, free or bound by a binder being read back
+    Neutral(Rc<Neutral>),
+}
+
+struct Closure {
+    param: String,
+    body: Term,
+    env: Env,
+}
+
+struct Neutral {
+    head: String,
+    args: Vec<usize>,
+}
+
+type Env = Option<Rc<Binding>>;
+
+struct Binding {
+    name: String,
+    cell: usize,
+    next: Env,
+}
+
+// Why the machine stopped before a normal form
+enum Stop {
+    Limit,
+    Depth,
+    Cancelled,
+    // A definition needs its own value to be evaluated
+    Loop(String),
+}
+
+impl<'a> Machine<'a> {
+    pub(crate) fn new(
+        definitions: &'a Definitions,
+        max_steps: Option<usize>,
+        max_depth: Option<usize>,
+        cancel: Option<Arc<AtomicBool>>,
+    ) -> Self {
+        Machine {
+            heap: Vec::new(),
+            definitions,
+            unfolded: HashMap::new(),
+            globals: HashSet::new(),
+            max_steps,
+            max_depth,
+            cancel,
+            beta: 0,
+            delta: 0,
+        }
+    }
+
+    pub(crate) fn run(mut self, expr: &Expr) -> Result<ByNeed> {
+        self.globals = expr.free_vars();
+        for definition in self.definitions.values() {
+            self.globals.extend(definition.free_vars());
+        }
+
+        let term = Term::new(expr.clone());
+        let result = self
+            .whnf(&term, &None)
+            .and_then(|value| self.read_back(value, &mut Vec::new(), 1));
+        let (result, termination) = match result {
+            Ok(result) => (
+                Some(result.simplify_numbered_vars().0),
+                Termination::NormalForm,
+            ),
+            Err(Stop::Limit) => (None, Termination::StepLimit),
+            Err(Stop::Depth) => (None, Termination::DepthLimit),
+            Err(Stop::Cancelled) => (None, Termination::Cancelled),
+            Err(Stop::Loop(name)) => {
+                return Err(anyhow!("Definition '{}' needs its own value", name));
+            }
+        };
+        Ok(ByNeed {
+            result,
+            beta: self.beta,
+            delta: self.delta,
+            termination,
+        })
+    }
+
+    // Counts a step, failing once over the limit or cancelled
+    fn tick(&self) -> Result<(), Stop> {
+        if self
+            .max_steps
+            .is_some_and(|max| self.beta + self.delta >= max)
+        {
+            return Err(Stop::Limit);
+        }
+        self.check_cancel()
+    }
+
+    // Reading back takes no step, a normal form reached at the limit is still read
+    fn check_cancel(&self) -> Result<(), Stop> {
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+        {
+            return Err(Stop::Cancelled);
+        }
+        Ok(())
+    }
+
+    // Unwinds the spine of applications, pushing each argument as a thunk, and applies
+    // the head to them
+    fn whnf(&mut self, term: &Term, env: &Env) -> Result<Value, Stop> {
+        let mut term = term.clone();
+        let mut env = env.clone();
+        // The next argument to apply is on top
+        let mut args = Vec::new();
+        loop {
+            let value = match &*term {
+                Expr::Apl(func, arg) => {
+                    args.push(self.delay(arg, &env));
+                    term = func.clone();
+                    continue;
+                }
+                Expr::Abs(param, body) => Value::Closure(Rc::new(Closure {
+                    param: param.clone(),
+                    body: body.clone(),
+                    env: env.clone(),
+                })),
+                Expr::Var(name) => self.lookup(name, &env)?,
+            };
+
+            let Some(arg) = args.pop() else {
+                return Ok(value);
+            };
+            match value {
+                Value::Closure(closure) => {
+                    self.tick()?;
+                    self.beta += 1;
+                    env = bind(&closure.env, &closure.param, arg);
+                    term = closure.body.clone();
+                }
+                Value::Neutral(neutral) => {
+                    let mut applied = neutral.args.clone();
+                    applied.push(arg);
+                    applied.extend(args.drain(..).rev());
+                    return Ok(Value::Neutral(Rc::new(Neutral {
+                        head: neutral.head.clone(),
+                        args: applied,
+                    })));
+                }
+            }
+        }
+    }
+
+    // A variable is passed on as the thunk it is bound to, anything else gets a new one
+    fn delay(&mut self, term: &Term, env: &Env) -> usize {
+        if let Expr::Var(name) = &**term
+            && let Some(cell) = find(env, name)
+        {
+            return cell;
+        }
+        self.alloc(Cell::Thunk(term.clone(), env.clone()))
+    }
+
+    fn lookup(&mut self, name: &str, env: &Env) -> Result<Value, Stop> {
+        if let Some(cell) = find(env, name) {
+            return self.force(cell);
+        }
+        let Some(definition) = self.definitions.get(name) else {
+            return Ok(neutral(name));
+        };
+        let cell = match self.unfolded.get(name) {
+            Some(&cell) => cell,
+            None => {
+                self.tick()?;
+                self.delta += 1;
+                let cell = self.alloc(Cell::Thunk(Term::new(definition.clone()), None));
+                self.unfolded.insert(name.to_string(), cell);
+                cell
+            }
+        };
+        self.force(cell)
+    }
+
+    fn force(&mut self, cell: usize) -> Result<Value, Stop> {
+        match std::mem::replace(&mut self.heap[cell], Cell::Busy) {
+            Cell::Value(value) => {
+                self.heap[cell] = Cell::Value(value.clone());
+                Ok(value)
+            }
+            Cell::Busy => Err(Stop::Loop(self.definition_of(cell))),
+            Cell::Thunk(term, env) => {
+                let value = self.whnf(&term, &env)?;
+                self.heap[cell] = Cell::Value(value.clone());
+                Ok(value)
+            }
+        }
+    }
+
+    // Reads a value back as a term in normal form, going under binders with variables
+    // named apart from the enclosing ones in `scope`. The term read back is at `depth`
+    // in the normal form, deeper than the limit it stops.
+    fn read_back(
+        &mut self,
+        value: Value,
+        scope: &mut Vec<String>,
+        depth: usize,
+    ) -> Result<Expr, Stop> {
+        if self.max_depth.is_some_and(|max| depth > max) {
+            return Err(Stop::Depth);
+        }
+        match value {
+            Value::Closure(closure) => {
+                let name = self.fresh(&closure.param, scope);
+                let cell = self.alloc(Cell::Value(neutral(&name)));
+                let body = self.whnf(&closure.body, &bind(&closure.env, &closure.param, cell))?;
+                scope.push(name);
+                let body = self.read_back(body, scope, depth + 1);
+                let name = scope.pop().unwrap_or_default();
+                Ok(Expr::Abs(name, Term::new(body?)))
+            }
+            Value::Neutral(neutral) => {
+                let mut expr = Expr::Var(neutral.head.clone());
+                // The head is applied to the first argument deepest
+                for (i, &arg) in neutral.args.iter().enumerate() {
+                    self.check_cancel()?;
+                    let arg = self.force(arg)?;
+                    let arg = self.read_back(arg, scope, depth + neutral.args.len() - i)?;
+                    expr = Expr::Apl(Term::new(expr), Term::new(arg));
+                }
+                Ok(expr)
+            }
+        }
+    }
+
+    // `hint`, or `hint_1`, `hint_2`... when an enclosing binder or a free name has it
+    fn fresh(&self, hint: &str, scope: &[String]) -> String {
+        let taken = |name: &String| scope.contains(name) || self.globals.contains(name);
+        let mut name = hint.to_string();
+        let mut counter = 0;
+        while taken(&name) {
+            counter += 1;
+            name = format!("{}_{}", hint, counter);
+        }
+        name
+    }
+
+    // Only the thunk of a definition can be needed while it is evaluated
+    fn definition_of(&self, cell: usize) -> String {
+        self.unfolded
+            .iter()
+            .find(|(_, other)| **other == cell)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_default()
+    }
+
+    fn alloc(&mut self, cell: Cell) -> usize {
+        self.heap.push(cell);
+        self.heap.len() - 1
+    }
+}
+
+fn bind(env: &Env, name: &str, cell: usize) -> Env {
+    Some(Rc::new(Binding {
+        name: name.to_string(),
+        cell,
+        next: env.clone(),
+    }))
+}
+
+fn find(env: &Env, name: &str) -> Option<usize> {
+    let mut env = env.as_ref();
+    while let Some(binding) = env {
+        if binding.name == name {
+            return Some(binding.cell);
+        }
+        env = binding.next.as_ref();
+    }
+    None
+}
+
+fn neutral(name: &str) -> Value {
+    Value::Neutral(Rc::new(Neutral {
+        head: name.to_string(),
+        args: Vec::new(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::{Termination, Vm};
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    // A normal form that reads back forever, `x = a (a (a ...))`
+    fn cyclic() -> (Vm, crate::ast::Expr) {
+        let mut vm = Vm::new();
+        vm.define("x", "a x").unwrap();
+        let expr = vm.parse("x").unwrap();
+        (vm, expr)
+    }
+
+    #[test]
+    fn read_back_stops_at_the_depth_limit() {
+        let (mut vm, expr) = cyclic();
+        vm.set_max_depth(Some(100));
+        let by_need = vm.evaluate_by_need(&expr).unwrap();
+        assert_eq!(by_need.termination, Termination::DepthLimit);
+        assert!(by_need.result.is_none());
+        assert_eq!(by_need.delta, 1);
+    }
+
+    #[test]
+    fn read_back_stops_when_cancelled() {
+        let (mut vm, expr) = cyclic();
+        let cancel = Arc::new(AtomicBool::new(true));
+        vm.set_cancel_flag(cancel);
+        let by_need = vm.evaluate_by_need(&expr).unwrap();
+        assert_eq!(by_need.termination, Termination::Cancelled);
+    }
+
+    #[test]
+    fn depth_limit_allows_normal_forms_within_it() {
+        let mut vm = Vm::new();
+        vm.set_max_depth(Some(4));
+        let expr = vm.parse("(\\x.a x x) b").unwrap();
+        let by_need = vm.evaluate_by_need(&expr).unwrap();
+        assert_eq!(by_need.termination, Termination::NormalForm);
+        assert_eq!(by_need.result.unwrap().depth(), 3);
+
+        vm.set_max_depth(Some(2));
+        let by_need = vm.evaluate_by_need(&expr).unwrap();
+        assert_eq!(by_need.termination, Termination::DepthLimit);
+    }
+}
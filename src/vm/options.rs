@@ -0,0 +1,15 @@
+use crate::ast::Strategy;
+
+/// Settings a `Vm` starts with, see `Vm::with_options`.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub strategy: Strategy,
+    /// Reductions a single evaluation may perform, `None` means no limit.
+    pub max_steps: Option<usize>,
+    /// Largest term, in nodes, an evaluation may build before giving up.
+    pub max_size: Option<usize>,
+    /// Deepest term an evaluation may build before giving up.
+    pub max_depth: Option<usize>,
+    /// Definitions loaded up front, one `name := body` per line.
+    pub prelude: String,
+}
@@ -0,0 +1,63 @@
+use super::{Step, Termination, Vm};
+
+/// Reductions of the current expression of a `Vm`, computed one at a time.
+pub struct Steps<'a> {
+    vm: &'a mut Vm,
+    taken: usize,
+    termination: Option<Termination>,
+}
+
+impl<'a> Steps<'a> {
+    pub(crate) fn new(vm: &'a mut Vm, taken: usize) -> Self {
+        Steps {
+            vm,
+            taken,
+            termination: None,
+        }
+    }
+
+    /// Why the iteration ended, `None` while steps remain.
+    pub fn termination(&self) -> Option<Termination> {
+        self.termination
+    }
+}
+
+impl Iterator for Steps<'_> {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        if self.termination.is_some() {
+            return None;
+        }
+        let termination = if self.vm.take_stopped() {
+            Termination::Stopped
+        } else if self.vm.is_cancelled() {
+            Termination::Cancelled
+        } else if self.vm.is_normal_form() {
+            // Checked before the limits, a normal form reached at them still counts
+            Termination::NormalForm
+        } else if self.vm.max_steps.is_some_and(|max| self.taken >= max) {
+            Termination::StepLimit
+        } else if let Some(expr) = &self.vm.current_expr
+            && self.vm.max_size.is_some_and(|max| expr.size() > max)
+        {
+            Termination::SizeLimit
+        } else if let Some(expr) = &self.vm.current_expr
+            && self.vm.max_depth.is_some_and(|max| expr.depth() > max)
+        {
+            Termination::DepthLimit
+        } else {
+            match self.vm.step() {
+                Ok(Some(step)) => {
+                    self.taken += 1;
+                    return Some(step);
+                }
+                Ok(None) if self.vm.take_stopped() => Termination::Stopped,
+                // Without an expression there is nothing left to reduce
+                Ok(None) | Err(_) => Termination::NormalForm,
+            }
+        };
+        self.termination = Some(termination);
+        None
+    }
+}
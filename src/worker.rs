@@ -0,0 +1,105 @@
+use anyhow::Result;
+use rambda::{
+    ast::Expr,
+    vm::{Stats, Step, Termination, Vm},
+};
+use std::{
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+// Messages the session has not read yet, the worker waits past this
+const CHANNEL_BOUND: usize = 1024;
+// Steps between two `Count`s when the steps themselves are not sent
+const COUNT_EVERY: usize = 1000;
+/// Stack of the worker threads and of the session. Reducing and printing a term recurses
+/// as deep as the term is, up to the session's depth limit.
+pub const STACK_SIZE: usize = 256 << 20;
+
+/// What an evaluation on a worker thread reports.
+pub enum Progress {
+    Step(Step),
+    Count {
+        taken: usize,
+        size: usize,
+    },
+    /// Always the last message, `result` is the term the evaluation stopped at.
+    Done {
+        termination: Termination,
+        taken: usize,
+        result: Expr,
+        stats: Stats,
+    },
+    /// A line of what a job prints.
+    Line(String),
+    /// Always the last message of a job.
+    Finished(Result<()>),
+}
+
+/// Work other than a reduction, for `spawn_job`. It prints through the function it is
+/// given.
+pub type Job = Box<dyn FnOnce(Vm, &mut dyn FnMut(String)) -> Result<()> + Send>;
+
+/// Reduces `expr` with `vm` on its own thread. Every step is sent when `trace` is set,
+/// otherwise a `Count` now and then. The worker ends early once the receiver is dropped.
+pub fn spawn(mut vm: Vm, expr: Expr, trace: bool) -> Receiver<Progress> {
+    let (sender, receiver) = mpsc::sync_channel(CHANNEL_BOUND);
+    spawn_thread(move || {
+        vm.set_expr(expr);
+        let mut taken = 0;
+        let mut steps = vm.steps();
+        for step in steps.by_ref() {
+            taken += 1;
+            let message = if trace {
+                Progress::Step(step)
+            } else if taken % COUNT_EVERY == 0 {
+                Progress::Count {
+                    taken,
+                    size: step.expr.size(),
+                }
+            } else {
+                continue;
+            };
+            if sender.send(message).is_err() {
+                return;
+            }
+        }
+
+        let termination = steps.termination().unwrap_or(Termination::NormalForm);
+        if termination == Termination::NormalForm {
+            for step in vm.simplify().unwrap_or_default() {
+                taken += 1;
+                if trace && sender.send(Progress::Step(step)).is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = sender.send(Progress::Done {
+            termination,
+            taken,
+            result: vm.get_expr().cloned().unwrap(),
+            stats: vm.stats().clone(),
+        });
+    });
+    receiver
+}
+
+/// Runs `job` with `vm` on its own thread, sending each line it prints as it comes.
+pub fn spawn_job(vm: Vm, job: Job) -> Receiver<Progress> {
+    let (sender, receiver) = mpsc::sync_channel(CHANNEL_BOUND);
+    spawn_thread(move || {
+        // Without a receiver the job still ends at the vm's limits or cancel flag
+        let result = job(vm, &mut |line| {
+            let _ = sender.send(Progress::Line(line));
+        });
+        let _ = sender.send(Progress::Finished(result));
+    });
+    receiver
+}
+
+fn spawn_thread(work: impl FnOnce() + Send + 'static) {
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(work)
+        .expect("failed to spawn a worker thread");
+}
@@ -0,0 +1,90 @@
+//! Runs `rambda eval` as a process, with a config folder and working directory of its own.
+
+use std::{
+    path::PathBuf,
+    process::{Command, Output},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const EXIT_PARSE_ERROR: i32 = 3;
+const EXIT_NO_NORMAL_FORM: i32 = 4;
+
+fn eval(args: &[&str]) -> Output {
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    let home: PathBuf = std::env::temp_dir().join(format!(
+        "rambda-eval-{}-{}",
+        std::process::id(),
+        RUNS.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&home).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rambda"))
+        .arg("eval")
+        .args(args)
+        .current_dir(&home)
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", &home)
+        .env_remove("NO_COLOR")
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&home).unwrap();
+    output
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+#[test]
+fn normal_form_exits_with_success() {
+    let output = eval(&["(\\x.x) a"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).ends_with("=> a (1 steps)"));
+}
+
+#[test]
+fn normal_form_at_the_step_limit_exits_with_success() {
+    let output = eval(&["-m", "1", "(\\x.x) a"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).ends_with("=> a (1 steps)"));
+
+    let output = eval(&["-m", "0", "a"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).ends_with("=> a (0 steps)"));
+
+    let output = eval(&["-m", "1", "(\\x.x) ((\\x.x) a)"]);
+    assert_eq!(output.status.code(), Some(EXIT_NO_NORMAL_FORM));
+}
+
+#[test]
+fn parse_error_exits_with_3() {
+    let output = eval(&["(\\x."]);
+    assert_eq!(output.status.code(), Some(EXIT_PARSE_ERROR));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Error:"));
+}
+
+#[test]
+fn step_limit_exits_with_4() {
+    let output = eval(&["-m", "5", "(\\x.x x) (\\x.x x)"]);
+    assert_eq!(output.status.code(), Some(EXIT_NO_NORMAL_FORM));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No normal form within 5 steps"));
+}
+
+#[test]
+fn machine_without_value_exits_with_4() {
+    let output = eval(&["-b", "krivine", "-m", "5", "(\\x.x x) (\\x.x x)"]);
+    assert_eq!(output.status.code(), Some(EXIT_NO_NORMAL_FORM));
+}
+
+#[test]
+fn json_reports_the_termination() {
+    let output = eval(&["-f", "json", "-m", "5", "(\\x.x x) (\\x.x x)"]);
+    assert_eq!(output.status.code(), Some(EXIT_NO_NORMAL_FORM));
+    let value: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(value["normalForm"], false);
+
+    let output = eval(&["-f", "json", "-q", "(\\x.x) a"]);
+    assert_eq!(output.status.code(), Some(0));
+    let value: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(value["normalForm"], true);
+    assert_eq!(value["result"], "a");
+}